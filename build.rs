@@ -30,11 +30,11 @@ fn version_short(info: &mut Info) -> String {
     if let Some(hash) = info.commit_hash_short() {
         let mut extra = String::new();
         extra.push_str(" (");
-        extra.push_str(&hash);
-        extra.push_str(" ");
+        extra.push_str(hash);
+        extra.push(' ');
         if let Some(date) = info.commit_date() {
-            extra.push_str(&date);
-            extra.push_str(")");
+            extra.push_str(date);
+            extra.push(')');
             version.push_str(&extra)
         }
     }
@@ -49,11 +49,11 @@ fn version_long(info: &mut Info) -> String {
     version.push_str(env!("CARGO_PKG_VERSION"));
     if let Some(hash) = info.commit_hash_long() {
         version.push_str("\ncommit-hash: ");
-        version.push_str(&hash);
+        version.push_str(hash);
     }
     if let Some(date) = info.commit_date() {
         version.push_str("\ncommit-date: ");
-        version.push_str(&date);
+        version.push_str(date);
     }
     version
 }
@@ -96,30 +96,30 @@ mod inner {
     }
 
     pub fn commit_hash_short() -> Option<String> {
-        let hash = command_stdout(Command::new(git()).args(&["show", "-s", "--format=%h"]));
+        let hash = command_stdout(Command::new(git()).args(["show", "-s", "--format=%h"]));
 
         match is_dirty() {
-            Some(id) if id == true => hash.map(|hash| format!("{}-dirty", hash)),
+            Some(true) => hash.map(|hash| format!("{}-dirty", hash)),
             _ => hash,
         }
     }
 
     pub fn commit_hash_long() -> Option<String> {
-        let hash = command_stdout(Command::new(git()).args(&["show", "-s", "--format=%H"]));
+        let hash = command_stdout(Command::new(git()).args(["show", "-s", "--format=%H"]));
 
         match is_dirty() {
-            Some(id) if id == true => hash.map(|hash| format!("{}-dirty", hash)),
+            Some(true) => hash.map(|hash| format!("{}-dirty", hash)),
             _ => hash,
         }
     }
 
     pub fn commit_date() -> Option<String> {
-        command_stdout(Command::new(git()).args(&["show", "-s", "--format=%ad", "--date=short"]))
+        command_stdout(Command::new(git()).args(["show", "-s", "--format=%ad", "--date=short"]))
     }
 
     pub fn is_dirty() -> Option<bool> {
         Command::new(git())
-            .args(&["diff-index", "--quiet", "HEAD"])
+            .args(["diff-index", "--quiet", "HEAD"])
             .status()
             .ok()
             .map(|status| !status.success())
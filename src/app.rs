@@ -1,49 +1,73 @@
 use crate::provider;
+use actix_web::dev::Service;
 use actix_web::{middleware, web, App, HttpServer};
 use data::Data;
-use handlers::{assets, providers, releases, repos, targets};
-use std::convert::TryFrom;
+use futures::Future;
+use handlers::{assets, health, installers, metrics, providers, releases, repos, targets};
 use std::error;
 use std::fmt;
 use std::io;
 use std::net::ToSocketAddrs;
-use updater::RepoUpdater;
+use std::time::Instant;
+use updater::Updaters;
 
-pub use config::{config, Config};
+pub use config::{config, Config, Factories, Factory};
 
 mod config;
 mod data;
 mod handlers;
 mod paths;
+mod reload;
+mod state;
 mod updater;
 
 pub fn run(config: Config) -> Result<(), Error> {
+    run_with_factories(config, &Factories::new())
+}
+
+/// Runs the server, building the release sources of any custom provider types with
+/// `factories`.
+pub fn run_with_factories(config: Config, factories: &Factories) -> Result<(), Error> {
     let addr = config.bind_addr;
-    let data = web::Data::new(Data::try_from(config)?);
+    let state_file = config.state_file.clone();
+    let updaters = Updaters::default();
+    let reloader = reload::Reloader::new(factories.clone(), updaters.clone(), &config);
+    let mut data = Data::from_config(config, factories)?;
+    data.set_updaters(updaters.clone());
+    let data = web::Data::new(data);
+    if let Some(ref path) = state_file {
+        state::restore(&data, path);
+    }
 
     let sys = actix_rt::System::new(env!("CARGO_PKG_NAME"));
-    schedule_updaters(data.clone());
+    updaters.spawn_missing(&data);
+    if let Some(reloader) = reloader {
+        reload::spawn_on_sighup(reloader, data.clone());
+    }
+    if let Some(path) = state_file {
+        state::spawn_saver(data.clone(), path);
+    }
     start_server(addr, data)?;
     Ok(sys.run()?)
 }
 
-fn schedule_updaters(data: web::Data<Data>) {
-    for provider in data.providers() {
-        for repo in provider.repos() {
-            updater::spawn(RepoUpdater::new(
-                data.clone(),
-                provider.domain(),
-                repo.owner(),
-                repo.name(),
-            ));
-        }
-    }
-}
-
 fn start_server<A: ToSocketAddrs>(addr: A, data: web::Data<Data>) -> Result<(), Error> {
     HttpServer::new(move || {
         App::new()
             .wrap(middleware::Logger::default())
+            .wrap_fn(|req, srv| {
+                let start = Instant::now();
+                let method = req.method().to_string();
+                srv.call(req).map(move |res| {
+                    crate::metrics::global().http_request(
+                        &metrics::route_label(res.request(), res.status()),
+                        &method,
+                        res.status().as_u16(),
+                        start.elapsed(),
+                    );
+                    res
+                })
+            })
             .register_data(data.clone())
             .configure(routes)
     })
@@ -54,7 +78,10 @@ fn start_server<A: ToSocketAddrs>(addr: A, data: web::Data<Data>) -> Result<(),
 }
 
 fn routes(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::scope("/v1").configure(providers));
+    cfg.service(web::resource("/healthz").route(web::get().to_async(health::get_healthz)))
+        .service(web::resource("/readyz").route(web::get().to_async(health::get_readyz)))
+        .service(web::resource("/metrics").route(web::get().to_async(metrics::get_metrics)))
+        .service(web::scope("/v1").configure(providers));
 }
 
 fn providers(cfg: &mut web::ServiceConfig) {
@@ -62,14 +89,25 @@ fn providers(cfg: &mut web::ServiceConfig) {
         web::scope("/providers.txt")
             .service(web::resource("").route(web::get().to_async(providers::get_providers_txt))),
     )
+    .service(
+        web::scope("/providers.json")
+            .service(web::resource("").route(web::get().to_async(providers::get_providers_json))),
+    )
     .service(web::scope("/providers").service(web::scope("{provider}").configure(repos)));
 }
 
 fn repos(cfg: &mut web::ServiceConfig) {
-    cfg.service(
+    cfg.service(web::scope("/status.json").service(
+        web::resource("").route(web::get().to_async(providers::get_provider_status_json)),
+    ))
+    .service(
         web::scope("/repos.txt")
             .service(web::resource("").route(web::get().to_async(repos::get_repos_txt))),
     )
+    .service(
+        web::scope("/repos.json")
+            .service(web::resource("").route(web::get().to_async(repos::get_repos_json))),
+    )
     .service(
         web::scope("/repos")
             .service(web::scope("/{owner}").service(web::scope("/{repo}").configure(releases))),
@@ -78,9 +116,21 @@ fn repos(cfg: &mut web::ServiceConfig) {
 
 fn releases(cfg: &mut web::ServiceConfig) {
     cfg.service(
+        web::scope("/install.sh")
+            .service(web::resource("").route(web::get().to_async(installers::get_install_sh))),
+    )
+    .service(
+        web::scope("/install.ps1")
+            .service(web::resource("").route(web::get().to_async(installers::get_install_ps1))),
+    )
+    .service(
         web::scope("/releases.txt")
             .service(web::resource("").route(web::get().to_async(releases::get_releases_txt))),
     )
+    .service(
+        web::scope("/releases.json")
+            .service(web::resource("").route(web::get().to_async(releases::get_releases_json))),
+    )
     .service(web::scope("/releases").service(web::scope("/{version}").configure(targets)));
 }
 
@@ -89,6 +139,10 @@ fn targets(cfg: &mut web::ServiceConfig) {
         web::scope("/targets.txt")
             .service(web::resource("").route(web::get().to_async(targets::get_targets_txt))),
     )
+    .service(
+        web::scope("/targets.json")
+            .service(web::resource("").route(web::get().to_async(targets::get_targets_json))),
+    )
     .service(web::scope("/targets").service(web::scope("/{target}").configure(assets)));
 }
 
@@ -98,28 +152,46 @@ fn assets(cfg: &mut web::ServiceConfig) {
             .service(web::resource("").route(web::get().to_async(assets::get_assets_txt))),
     )
     .service(
-        web::scope("/assets").service(
-            web::scope("/{asset}")
-                .service(web::resource("").route(web::get().to_async(assets::get_asset))),
-        ),
+        web::scope("/assets.json")
+            .service(web::resource("").route(web::get().to_async(assets::get_assets_json))),
+    )
+    .service(
+        web::scope("/assets")
+            .service(
+                web::resource("/{asset}.sha256")
+                    .route(web::get().to_async(assets::get_asset_sha256)),
+            )
+            .service(
+                web::scope("/{asset}")
+                    .service(web::resource("").route(web::get().to_async(assets::get_asset)))
+                    .service(
+                        web::resource("/verify").route(web::get().to_async(assets::verify_asset)),
+                    ),
+            ),
     );
 }
 
 #[derive(Debug)]
 pub enum Error {
+    Cache(io::Error),
     Config(provider::Error),
     ConfigLoad(Box<dyn error::Error + Send + Sync>),
     RepoConfig(&'static str),
     ServerInit(io::Error),
+    UnknownProvider(String),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            Error::Cache(ref err) => write!(f, "failed to open asset cache: {}", err),
             Error::Config(ref err) => err.fmt(f),
             Error::ConfigLoad(ref err) => err.fmt(f),
             Error::RepoConfig(ref msg) => write!(f, "{}", msg),
             Error::ServerInit(ref err) => err.fmt(f),
+            Error::UnknownProvider(ref provider) => {
+                write!(f, "no factory registered for provider type: {}", provider)
+            }
         }
     }
 }
@@ -127,10 +199,12 @@ impl fmt::Display for Error {
 impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
+            Error::Cache(ref err) => err.source(),
             Error::Config(ref err) => err.source(),
             Error::ConfigLoad(ref err) => err.source(),
             Error::RepoConfig(_) => None,
             Error::ServerInit(ref err) => err.source(),
+            Error::UnknownProvider(_) => None,
         }
     }
 }
@@ -1,7 +1,10 @@
 use crate::app::{Data, Error};
 use crate::env;
-use crate::{Provider, Registry};
+use crate::provider::github::auth;
+use crate::provider::{self, infer::TargetInference, Pagination, ReleaseSource};
+use crate::{Cache, Registry, TargetAliases};
 use log::info;
+use regex::Regex;
 use serde::de::{self, IntoDeserializer};
 use serde::{Deserialize, Deserializer};
 use std::collections::HashMap;
@@ -10,14 +13,20 @@ use std::fmt;
 use std::fs;
 use std::marker::PhantomData;
 use std::net::{self, SocketAddr};
-use std::path::Path;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 pub fn config(path: Option<&Path>) -> Result<Config, Error> {
     match path {
         Some(path) => {
             info!("loading config file; config={}", path.display());
-            Config::from_json_str(&fs::read_to_string(path)?)
+            let mut config = Config::from_json_str(&fs::read_to_string(path)?)?;
+            config.path = Some(path.to_path_buf());
+
+            Ok(config)
         }
         None => {
             info!("no config file found, using default config");
@@ -32,11 +41,64 @@ pub struct Config {
     pub bind_addr: SocketAddr,
     #[serde(default)]
     pub registry: HashMap<String, RegistryConfig>,
+    #[serde(default)]
+    pub cache: Option<CacheConfig>,
+    /// Where the release index is saved, to warm start from on the next run. The index is only
+    /// saved when this is set.
+    #[serde(default, deserialize_with = "de_opt_path")]
+    pub state_file: Option<PathBuf>,
+    /// Aliases which targets can be requested by, added to the built-in aliases for common
+    /// `os-arch` pairs and replacing any of the same name.
+    #[serde(default)]
+    pub target_aliases: HashMap<String, AliasTargets>,
+    /// Whether a requested `gnu` target which is missing falls back to its `musl` counterpart.
+    #[serde(default)]
+    pub musl_fallback: bool,
+    /// How many of a repo's update intervals may pass without a successful update before the
+    /// server reports that it is not ready. Staleness is not checked when this is `null`.
+    #[serde(default)]
+    pub stale_intervals: Option<NonZeroU32>,
+    /// The file which the config was loaded from, which is reloaded on `SIGHUP`.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+/// The targets which an alias stands for, given as either a single target or a list of targets
+/// in order of preference.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum AliasTargets {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl From<AliasTargets> for Vec<String> {
+    fn from(targets: AliasTargets) -> Self {
+        match targets {
+            AliasTargets::One(target) => vec![target],
+            AliasTargets::Many(targets) => targets,
+        }
+    }
+}
+
+/// Config for the on-disk asset cache.
+///
+/// When a cache is configured, remote assets are fetched through their provider and served from
+/// the cache, rather than clients being redirected to them.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheConfig {
+    /// The directory which the cache keeps its entries below, which may be shared with other
+    /// files.
+    #[serde(deserialize_with = "de_path")]
+    pub path: PathBuf,
+    /// The maximum total size of cached assets, in bytes.
+    pub max_size: u64,
 }
 
 impl Config {
     pub fn from_json_str(s: &str) -> Result<Self, Error> {
-        serde_json::from_str(&s).map_err(|err| Error::ConfigLoad(Box::new(err)))
+        serde_json::from_str(s).map_err(|err| Error::ConfigLoad(Box::new(err)))
     }
 }
 
@@ -45,43 +107,244 @@ impl Default for Config {
         Config {
             bind_addr: default_bind_addr(),
             registry: HashMap::new(),
+            cache: None,
+            state_file: None,
+            target_aliases: HashMap::new(),
+            musl_fallback: false,
+            stale_intervals: None,
+            path: None,
         }
     }
 }
 
+/// A function which builds a release source for a registry entry of a custom provider type.
+///
+/// It is called with the registry entry's name, its repos and any remaining settings from the
+/// entry.
+pub type Factory = dyn Fn(
+    String,
+    Vec<crate::Repo>,
+    serde_json::Map<String, serde_json::Value>,
+) -> Result<Box<dyn ReleaseSource>, provider::Error>;
+
+/// Factories for provider types which are implemented outside of this crate, keyed by the
+/// `"provider"` value which selects them in a registry entry.
+///
+/// The built in provider types always take precedence over a factory of the same name.
+#[derive(Clone, Default)]
+pub struct Factories(HashMap<String, Arc<Factory>>);
+
+impl Factories {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<S, F>(&mut self, provider: S, factory: F)
+    where
+        S: Into<String>,
+        F: Fn(
+                String,
+                Vec<crate::Repo>,
+                serde_json::Map<String, serde_json::Value>,
+            ) -> Result<Box<dyn ReleaseSource>, provider::Error>
+            + 'static,
+    {
+        let _ = self.0.insert(provider.into(), Arc::new(factory));
+    }
+
+    fn get(&self, provider: &str) -> Option<&Factory> {
+        self.0.get(provider).map(|factory| factory.as_ref())
+    }
+}
+
+impl fmt::Debug for Factories {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
 impl TryFrom<Config> for Data {
     type Error = Error;
 
     fn try_from(config: Config) -> Result<Self, Self::Error> {
-        let mut registry = Registry::new();
-        for (name, entry) in config.registry {
-            match entry {
-                RegistryConfig::GitHub { oauth_token, repos } => {
-                    use crate::provider::github::GitHub;
+        Self::from_config(config, &Factories::new())
+    }
+}
 
-                    registry.register(Provider::GitHub(GitHub::build(
-                        name,
-                        oauth_token,
-                        repos.into_iter().map(crate::Repo::from).collect::<Vec<_>>(),
-                    )?))
-                }
+impl Data {
+    /// Builds the application data from config, using `factories` to build the release sources
+    /// of any custom provider types.
+    pub fn from_config(config: Config, factories: &Factories) -> Result<Self, Error> {
+        let mut data = Self::new(build_registry(config.registry, factories)?);
+        if let Some(cache) = config.cache {
+            data.set_cache(Some(
+                Cache::open(cache.path, cache.max_size).map_err(Error::Cache)?,
+            ));
+        }
+        data.set_target_aliases(build_target_aliases(
+            config.target_aliases,
+            config.musl_fallback,
+        ));
+        data.set_stale_intervals(config.stale_intervals);
+
+        Ok(data)
+    }
+}
+
+/// Builds the registry from the config's registry entries, using `factories` to build the release
+/// sources of any custom provider types.
+pub(crate) fn build_registry(
+    entries: HashMap<String, RegistryConfig>,
+    factories: &Factories,
+) -> Result<Registry, Error> {
+    let mut registry = Registry::new();
+    for (name, entry) in entries {
+        match entry {
+            RegistryConfig::Filesystem { path, repos } => {
+                use crate::provider::filesystem::Filesystem;
+
+                registry.register(Box::new(Filesystem::build(
+                    name,
+                    path,
+                    repos.into_iter().map(crate::Repo::from).collect::<Vec<_>>(),
+                )?))
+            }
+            RegistryConfig::Gitea { oauth_token, repos } => {
+                use crate::provider::gitea::Gitea;
+
+                registry.register(Box::new(Gitea::build(
+                    name,
+                    oauth_token,
+                    repos.into_iter().map(crate::Repo::from).collect::<Vec<_>>(),
+                )?))
+            }
+            RegistryConfig::GitHub {
+                auth,
+                pagination,
+                repos,
+            } => {
+                use crate::provider::github::GitHub;
+
+                registry.register(Box::new(GitHub::build(
+                    name,
+                    auth,
+                    pagination,
+                    repos.into_iter().map(crate::Repo::from).collect::<Vec<_>>(),
+                )?))
+            }
+            RegistryConfig::GitLab { oauth_token, repos } => {
+                use crate::provider::gitlab::GitLab;
+
+                registry.register(Box::new(GitLab::build(
+                    name,
+                    oauth_token,
+                    repos.into_iter().map(crate::Repo::from).collect::<Vec<_>>(),
+                )?))
+            }
+            RegistryConfig::S3 {
+                endpoint,
+                bucket,
+                region,
+                access_key_id,
+                secret_access_key,
+                repos,
+            } => {
+                use crate::provider::s3::{client::Client, S3};
+
+                let endpoint =
+                    endpoint.unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", region));
+                let client =
+                    Client::build(endpoint, bucket, region, access_key_id, secret_access_key)
+                        .map_err(crate::provider::Error::from)?;
+
+                registry.register(Box::new(S3::build(
+                    name,
+                    client,
+                    repos.into_iter().map(crate::Repo::from).collect::<Vec<_>>(),
+                )?))
+            }
+            RegistryConfig::Custom {
+                provider,
+                repos,
+                settings,
+            } => {
+                let factory = factories
+                    .get(&provider)
+                    .ok_or_else(|| Error::UnknownProvider(provider.clone()))?;
+
+                registry.register(factory(
+                    name,
+                    repos.into_iter().map(crate::Repo::from).collect(),
+                    settings,
+                )?)
             }
         }
+    }
 
-        Ok(Self::new(registry))
+    Ok(registry)
+}
+
+/// Builds the target alias table from the built-in aliases and those of the config.
+pub(crate) fn build_target_aliases(
+    aliases: HashMap<String, AliasTargets>,
+    musl_fallback: bool,
+) -> TargetAliases {
+    let mut target_aliases = TargetAliases::default();
+    for (alias, targets) in aliases {
+        target_aliases.insert(alias, targets.into());
     }
+    target_aliases.set_musl_fallback(musl_fallback);
+
+    target_aliases
 }
 
 #[derive(Debug)]
 pub enum RegistryConfig {
+    Filesystem {
+        repos: Vec<Repo>,
+        path: PathBuf,
+    },
+    Gitea {
+        repos: Vec<Repo>,
+        oauth_token: String,
+    },
     GitHub {
+        repos: Vec<Repo>,
+        auth: auth::Auth,
+        pagination: Pagination,
+    },
+    GitLab {
         repos: Vec<Repo>,
         oauth_token: String,
     },
+    S3 {
+        repos: Vec<Repo>,
+        endpoint: Option<String>,
+        bucket: String,
+        region: String,
+        access_key_id: String,
+        secret_access_key: String,
+    },
+    Custom {
+        provider: String,
+        repos: Vec<Repo>,
+        settings: serde_json::Map<String, serde_json::Value>,
+    },
 }
 
 #[derive(Debug)]
-pub struct Repo(String, String);
+pub struct Repo {
+    owner: String,
+    name: String,
+    prereleases: bool,
+    drafts: bool,
+    proxy: Option<bool>,
+    interval: Option<Duration>,
+    splay: Option<Duration>,
+    infer_targets: TargetInference,
+    install_sh: Option<String>,
+    install_ps1: Option<String>,
+}
 
 impl FromStr for Repo {
     type Err = Error;
@@ -97,14 +360,206 @@ impl FromStr for Repo {
         } else if num_fields != 2 {
             unreachable!("invalid number of fields");
         } else {
-            Ok(Repo(fields[0].to_string(), fields[1].to_string()))
+            Ok(Repo {
+                owner: fields[0].to_string(),
+                name: fields[1].to_string(),
+                prereleases: false,
+                drafts: false,
+                proxy: None,
+                interval: None,
+                splay: None,
+                infer_targets: TargetInference::Off,
+                install_sh: None,
+                install_ps1: None,
+            })
         }
     }
 }
 
 impl From<Repo> for crate::Repo {
     fn from(repo: Repo) -> Self {
-        crate::Repo::new(repo.0, repo.1)
+        let mut converted = crate::Repo::new(repo.owner, repo.name);
+        converted.set_prereleases(repo.prereleases);
+        converted.set_drafts(repo.drafts);
+        converted.set_proxy(repo.proxy.unwrap_or(false));
+        if let Some(interval) = repo.interval {
+            converted.set_interval(interval);
+        }
+        if let Some(splay) = repo.splay {
+            converted.set_splay(splay);
+        }
+        converted.set_target_inference(repo.infer_targets);
+        converted.set_install_sh(repo.install_sh);
+        converted.set_install_ps1(repo.install_ps1);
+
+        converted
+    }
+}
+
+/// Deserializes a repo from either an `"owner/name"` string or a map of repo config data.
+struct RepoVisitor;
+
+impl<'de> de::Visitor<'de> for RepoVisitor {
+    type Value = Repo;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an owner/name string or a map of repo config data")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        v.parse().map_err(de::Error::custom)
+    }
+
+    fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+    where
+        M: de::MapAccess<'de>,
+    {
+        const VALID_FIELDS: &[&str] = &[
+            "repo",
+            "prereleases",
+            "drafts",
+            "proxy",
+            "interval",
+            "splay",
+            "infer_targets",
+            "installers",
+        ];
+
+        let mut repo = None::<Repo>;
+        let mut prereleases = None::<bool>;
+        let mut drafts = None::<bool>;
+        let mut proxy = None::<bool>;
+        let mut interval = None::<Duration>;
+        let mut splay = None::<Duration>;
+        let mut infer_targets = None::<InferTargets>;
+        let mut installers = None::<InstallersConfig>;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "repo" => {
+                    let val = map.next_value::<String>()?;
+                    repo = Some(val.parse().map_err(de::Error::custom)?);
+                }
+                "prereleases" => {
+                    prereleases = Some(map.next_value()?);
+                }
+                "drafts" => {
+                    drafts = Some(map.next_value()?);
+                }
+                "proxy" => {
+                    proxy = Some(map.next_value()?);
+                }
+                "interval" => {
+                    interval = Some(interval_secs(map.next_value()?)?);
+                }
+                "splay" => {
+                    splay = Some(Duration::from_secs(map.next_value()?));
+                }
+                "infer_targets" => {
+                    infer_targets = Some(map.next_value()?);
+                }
+                "installers" => {
+                    installers = Some(map.next_value()?);
+                }
+                unknown => {
+                    return Err(de::Error::unknown_field(unknown, VALID_FIELDS));
+                }
+            }
+        }
+
+        let mut repo = repo.ok_or_else(|| de::Error::missing_field("repo"))?;
+        repo.prereleases = prereleases.unwrap_or(false);
+        repo.drafts = drafts.unwrap_or(false);
+        repo.proxy = proxy;
+        repo.interval = interval;
+        repo.splay = splay;
+        repo.infer_targets = infer_targets.map(|i| i.0).unwrap_or(TargetInference::Off);
+        if let Some(installers) = installers {
+            repo.install_sh = installers.sh.map(read_template).transpose()?;
+            repo.install_ps1 = installers.ps1.map(read_template).transpose()?;
+        }
+
+        Ok(repo)
+    }
+}
+
+/// Paths of templates which override a repo's built-in installers.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InstallersConfig {
+    #[serde(default, deserialize_with = "de_opt_path")]
+    sh: Option<PathBuf>,
+    #[serde(default, deserialize_with = "de_opt_path")]
+    ps1: Option<PathBuf>,
+}
+
+/// Reads an installer template, so that a missing template is reported when the config is loaded.
+fn read_template<E: de::Error>(path: PathBuf) -> Result<String, E> {
+    fs::read_to_string(&path).map_err(|err| {
+        de::Error::custom(format!(
+            "failed to read installer template {}: {}",
+            path.display(),
+            err
+        ))
+    })
+}
+
+/// How a repo infers targets from asset file names, deserialized from either a boolean which
+/// turns on the matching of Rust target triples or from a list of regex patterns.
+struct InferTargets(TargetInference);
+
+struct InferTargetsVisitor;
+
+impl<'de> de::Visitor<'de> for InferTargetsVisitor {
+    type Value = InferTargets;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a boolean or a list of regex patterns with a `target` capture group")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(InferTargets(if v {
+            TargetInference::Triples
+        } else {
+            TargetInference::Off
+        }))
+    }
+
+    fn visit_seq<S>(self, mut seq: S) -> Result<Self::Value, S::Error>
+    where
+        S: de::SeqAccess<'de>,
+    {
+        let mut patterns = Vec::new();
+        while let Some(pattern) = seq.next_element::<String>()? {
+            let regex = Regex::new(&pattern).map_err(de::Error::custom)?;
+            if !regex.capture_names().any(|name| name == Some("target")) {
+                return Err(de::Error::custom(format!(
+                    "pattern has no `target` capture group: {}",
+                    pattern
+                )));
+            }
+            patterns.push(regex);
+        }
+
+        Ok(InferTargets(TargetInference::Patterns(patterns)))
+    }
+}
+
+impl<'de> Deserialize<'de> for InferTargets {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(InferTargetsVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Repo {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(RepoVisitor)
     }
 }
 
@@ -127,12 +582,42 @@ impl<'de> de::Visitor<'de> for RegistryConfigVisitor {
     where
         M: de::MapAccess<'de>,
     {
-        const VALID_FIELDS: &[&str] = &["provider", "oauth_token", "repos"];
+        const VALID_FIELDS: &[&str] = &[
+            "provider",
+            "oauth_token",
+            "app",
+            "path",
+            "endpoint",
+            "bucket",
+            "region",
+            "access_key_id",
+            "secret_access_key",
+            "per_page",
+            "max_releases",
+            "proxy",
+            "interval",
+            "splay",
+            "repos",
+        ];
+        const BUILTIN_PROVIDERS: &[&str] =
+            &["filesystem", "gitea", "forgejo", "github", "gitlab", "s3"];
         const DEFAULT_PROVIDER: &str = "github";
+        const DEFAULT_REGION: &str = "us-east-1";
 
         let mut provider = None::<&str>;
         let mut oauth_token = None::<String>;
-        let mut repos = None::<Vec<&str>>;
+        let mut app = None::<GitHubAppConfig>;
+        let mut path = None::<String>;
+        let mut endpoint = None::<String>;
+        let mut bucket = None::<String>;
+        let mut region = None::<String>;
+        let mut access_key_id = None::<String>;
+        let mut secret_access_key = None::<String>;
+        let mut per_page = None::<u32>;
+        let mut max_releases = None::<usize>;
+        let mut defaults = RepoDefaults::default();
+        let mut settings = serde_json::Map::new();
+        let mut repos = None::<Vec<Repo>>;
 
         while let Some(key) = map.next_key()? {
             match key {
@@ -144,36 +629,240 @@ impl<'de> de::Visitor<'de> for RegistryConfigVisitor {
                     env::replace_vars(&mut val).map_err(de::Error::custom)?;
                     oauth_token = Some(val);
                 }
+                "app" => {
+                    app = Some(map.next_value()?);
+                }
+                "path" => {
+                    let mut val = map.next_value()?;
+                    env::replace_vars(&mut val).map_err(de::Error::custom)?;
+                    path = Some(val);
+                }
+                "endpoint" => {
+                    let mut val = map.next_value()?;
+                    env::replace_vars(&mut val).map_err(de::Error::custom)?;
+                    endpoint = Some(val);
+                }
+                "bucket" => {
+                    bucket = Some(map.next_value()?);
+                }
+                "region" => {
+                    region = Some(map.next_value()?);
+                }
+                "access_key_id" => {
+                    let mut val = map.next_value()?;
+                    env::replace_vars(&mut val).map_err(de::Error::custom)?;
+                    access_key_id = Some(val);
+                }
+                "secret_access_key" => {
+                    let mut val = map.next_value()?;
+                    env::replace_vars(&mut val).map_err(de::Error::custom)?;
+                    secret_access_key = Some(val);
+                }
+                "per_page" => {
+                    let val = map.next_value()?;
+                    if !(1..=100).contains(&val) {
+                        return Err(de::Error::invalid_value(
+                            de::Unexpected::Unsigned(u64::from(val)),
+                            &"a page size between 1 and 100",
+                        ));
+                    }
+                    per_page = Some(val);
+                }
+                "max_releases" => {
+                    max_releases = Some(map.next_value()?);
+                }
+                "proxy" => {
+                    defaults.proxy = Some(map.next_value()?);
+                }
+                "interval" => {
+                    defaults.interval = Some(interval_secs(map.next_value()?)?);
+                }
+                "splay" => {
+                    defaults.splay = Some(Duration::from_secs(map.next_value()?));
+                }
                 "repos" => {
                     repos = Some(map.next_value()?);
                 }
                 unknown => {
-                    return Err(de::Error::unknown_field(unknown, VALID_FIELDS));
+                    let mut val = map.next_value()?;
+                    if let serde_json::Value::String(ref mut s) = val {
+                        env::replace_vars(s).map_err(de::Error::custom)?;
+                    }
+                    settings.insert(unknown.to_string(), val);
                 }
             }
         }
 
-        match provider.unwrap_or_else(|| DEFAULT_PROVIDER) {
+        let provider = provider.unwrap_or(DEFAULT_PROVIDER);
+        if BUILTIN_PROVIDERS.contains(&provider) {
+            if let Some(unknown) = settings.keys().next() {
+                return Err(de::Error::unknown_field(unknown, VALID_FIELDS));
+            }
+        }
+        if provider != "github" {
+            let field = match (per_page, max_releases) {
+                (Some(_), _) => Some("per_page"),
+                (None, Some(_)) => Some("max_releases"),
+                (None, None) => None,
+            };
+            if let Some(field) = field {
+                return Err(de::Error::custom(format!(
+                    "{} is only supported by the github provider; provider={}",
+                    field, provider
+                )));
+            }
+        }
+
+        match provider {
+            "filesystem" => {
+                let path = path.ok_or_else(|| de::Error::missing_field("path"))?.into();
+                let repos = parse_repos(repos, &defaults)?;
+
+                Ok(RegistryConfig::Filesystem { path, repos })
+            }
+            "gitea" | "forgejo" => {
+                let oauth_token =
+                    oauth_token.ok_or_else(|| de::Error::missing_field("oauth_token"))?;
+                let repos = parse_repos(repos, &defaults)?;
+
+                Ok(RegistryConfig::Gitea { oauth_token, repos })
+            }
             "github" => {
+                let auth = match (oauth_token, app) {
+                    (Some(oauth_token), None) => auth::Auth::Token(oauth_token),
+                    (None, Some(app)) => auth::Auth::App(app.build()?),
+                    (Some(_), Some(_)) => {
+                        return Err(de::Error::custom(
+                            "only one of oauth_token or app may be given",
+                        ))
+                    }
+                    (None, None) => auth::Auth::Anonymous,
+                };
+                let repos = parse_repos(repos, &defaults)?;
+                if let auth::Auth::Anonymous = auth {
+                    // GitHub only lists draft releases to users who can push to the repo
+                    if let Some(repo) = repos.iter().find(|repo| repo.drafts) {
+                        return Err(de::Error::custom(format!(
+                            "drafts requires oauth_token or app, as draft releases are hidden \
+                            from anonymous requests; repo={}/{}",
+                            repo.owner, repo.name
+                        )));
+                    }
+                }
+
+                let mut pagination = Pagination::default();
+                if let Some(per_page) = per_page {
+                    pagination.per_page = per_page;
+                }
+                pagination.max_releases = max_releases;
+
+                Ok(RegistryConfig::GitHub {
+                    auth,
+                    pagination,
+                    repos,
+                })
+            }
+            "gitlab" => {
                 let oauth_token =
                     oauth_token.ok_or_else(|| de::Error::missing_field("oauth_token"))?;
-                let repos = repos
-                    .ok_or_else(|| de::Error::missing_field("repos"))?
-                    .into_iter()
-                    .map(|repo| repo.parse())
-                    .collect::<Result<Vec<_>, _>>()
-                    .map_err(de::Error::custom)?;
+                let repos = parse_repos(repos, &defaults)?;
 
-                Ok(RegistryConfig::GitHub { oauth_token, repos })
+                Ok(RegistryConfig::GitLab { oauth_token, repos })
+            }
+            "s3" => {
+                let bucket = bucket.ok_or_else(|| de::Error::missing_field("bucket"))?;
+                let region = region.unwrap_or_else(|| DEFAULT_REGION.to_string());
+                let access_key_id =
+                    access_key_id.ok_or_else(|| de::Error::missing_field("access_key_id"))?;
+                let secret_access_key = secret_access_key
+                    .ok_or_else(|| de::Error::missing_field("secret_access_key"))?;
+                let repos = parse_repos(repos, &defaults)?;
+
+                Ok(RegistryConfig::S3 {
+                    repos,
+                    endpoint,
+                    bucket,
+                    region,
+                    access_key_id,
+                    secret_access_key,
+                })
+            }
+            custom => {
+                let repos = parse_repos(repos, &defaults)?;
+
+                Ok(RegistryConfig::Custom {
+                    provider: custom.to_string(),
+                    repos,
+                    settings,
+                })
             }
-            unexpected => Err(de::Error::invalid_value(
-                de::Unexpected::Str(unexpected),
-                &"a valid provider type",
-            )),
         }
     }
 }
 
+/// Credentials of a GitHub App installation, which a `github` registry entry can authenticate
+/// with in place of an `oauth_token`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GitHubAppConfig {
+    app_id: u64,
+    installation_id: u64,
+    /// The path of the app's PEM encoded private key.
+    #[serde(deserialize_with = "de_path")]
+    private_key: PathBuf,
+}
+
+impl GitHubAppConfig {
+    /// Reads the app's private key, so that a missing or invalid key is reported with the config.
+    fn build<E: de::Error>(self) -> Result<auth::App, E> {
+        let pem = fs::read_to_string(&self.private_key).map_err(|err| {
+            de::Error::custom(format!(
+                "failed to read github app private key {}: {}",
+                self.private_key.display(),
+                err
+            ))
+        })?;
+
+        auth::App::from_pem(self.app_id, self.installation_id, &pem).map_err(de::Error::custom)
+    }
+}
+
+/// Repo settings of a registry entry, which apply to any of its repos which do not set their own.
+#[derive(Debug, Default)]
+struct RepoDefaults {
+    proxy: Option<bool>,
+    interval: Option<Duration>,
+    splay: Option<Duration>,
+}
+
+/// Checks that repos were given, applying the registry entry's repo settings to any repos which
+/// do not set their own.
+fn parse_repos<E: de::Error>(
+    repos: Option<Vec<Repo>>,
+    defaults: &RepoDefaults,
+) -> Result<Vec<Repo>, E> {
+    let mut repos = repos.ok_or_else(|| de::Error::missing_field("repos"))?;
+    for repo in &mut repos {
+        repo.proxy = repo.proxy.or(defaults.proxy);
+        repo.interval = repo.interval.or(defaults.interval);
+        repo.splay = repo.splay.or(defaults.splay);
+    }
+
+    Ok(repos)
+}
+
+/// Converts an update interval given in seconds, which must be at least one second.
+fn interval_secs<E: de::Error>(secs: u64) -> Result<Duration, E> {
+    if secs == 0 {
+        return Err(de::Error::invalid_value(
+            de::Unexpected::Unsigned(secs),
+            &"an interval of at least 1 second",
+        ));
+    }
+
+    Ok(Duration::from_secs(secs))
+}
+
 impl<'de> Deserialize<'de> for RegistryConfig {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -197,3 +886,27 @@ where
     env::replace_vars(&mut s).map_err(de::Error::custom)?;
     SocketAddr::deserialize(s.into_deserializer())
 }
+
+/// Deserialize into a `PathBuf` by first replacing any environment variables.
+fn de_path<'de, D>(deserializer: D) -> Result<PathBuf, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut s = String::deserialize(deserializer)?;
+    env::replace_vars(&mut s).map_err(de::Error::custom)?;
+    Ok(PathBuf::from(s))
+}
+
+/// Deserialize into an optional `PathBuf` by first replacing any environment variables.
+fn de_opt_path<'de, D>(deserializer: D) -> Result<Option<PathBuf>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(mut s) => {
+            env::replace_vars(&mut s).map_err(de::Error::custom)?;
+            Ok(Some(PathBuf::from(s)))
+        }
+        None => Ok(None),
+    }
+}
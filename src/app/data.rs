@@ -1,20 +1,84 @@
-use crate::{Provider, Registry};
+use super::updater::Updaters;
+use crate::{Cache, Registry, ReleaseSource, TargetAliases};
+use std::num::NonZeroU32;
+use std::sync::{Arc, RwLock};
 
+/// The application data shared by every request handler.
+///
+/// The registry and the target aliases can be replaced while the server runs, when the config is
+/// reloaded. Each accessor returns a snapshot which stays valid for as long as it is held, so a
+/// request which is in flight during a reload completes against the data it started with.
 #[derive(Debug)]
 pub struct Data {
-    registry: Registry,
+    registry: RwLock<Arc<Registry>>,
+    cache: Option<Arc<Cache>>,
+    target_aliases: RwLock<Arc<TargetAliases>>,
+    stale_intervals: RwLock<Option<NonZeroU32>>,
+    updaters: Updaters,
 }
 
 impl Data {
     pub fn new(registry: Registry) -> Self {
-        Self { registry }
+        Self {
+            registry: RwLock::new(Arc::new(registry)),
+            cache: None,
+            target_aliases: RwLock::new(Arc::new(TargetAliases::default())),
+            stale_intervals: RwLock::new(None),
+            updaters: Updaters::default(),
+        }
     }
 
-    pub fn provider<S: AsRef<str>>(&self, key: S) -> Option<&Provider> {
-        self.registry.get(key.as_ref())
+    pub fn provider<S: AsRef<str>>(&self, key: S) -> Option<Arc<dyn ReleaseSource>> {
+        self.registry().get(key.as_ref())
     }
 
-    pub fn providers(&self) -> impl Iterator<Item = &Provider> {
-        self.registry.iter()
+    pub fn providers(&self) -> Vec<Arc<dyn ReleaseSource>> {
+        self.registry().iter().cloned().collect()
+    }
+
+    pub fn registry(&self) -> Arc<Registry> {
+        self.registry.read().expect("lock poisoned").clone()
+    }
+
+    /// Replaces the registry, such as when the config is reloaded.
+    pub fn set_registry(&self, registry: Registry) {
+        *self.registry.write().expect("lock poisoned") = Arc::new(registry);
+    }
+
+    /// Returns the asset cache, if one is configured.
+    pub fn cache(&self) -> Option<Arc<Cache>> {
+        self.cache.clone()
+    }
+
+    pub fn set_cache(&mut self, cache: Option<Cache>) {
+        self.cache = cache.map(Arc::new);
+    }
+
+    /// Returns the aliases which targets can be requested by.
+    pub fn target_aliases(&self) -> Arc<TargetAliases> {
+        self.target_aliases.read().expect("lock poisoned").clone()
+    }
+
+    pub fn set_target_aliases(&self, target_aliases: TargetAliases) {
+        *self.target_aliases.write().expect("lock poisoned") = Arc::new(target_aliases);
+    }
+
+    /// Returns how many update intervals a repo may go without a successful update before the
+    /// server is not ready, if staleness is checked.
+    pub fn stale_intervals(&self) -> Option<NonZeroU32> {
+        *self.stale_intervals.read().expect("lock poisoned")
+    }
+
+    pub fn set_stale_intervals(&self, stale_intervals: Option<NonZeroU32>) {
+        *self.stale_intervals.write().expect("lock poisoned") = stale_intervals;
+    }
+
+    /// Returns the updaters of the registry's repos.
+    pub fn updaters(&self) -> &Updaters {
+        &self.updaters
+    }
+
+    pub fn set_updaters(&mut self, updaters: Updaters) {
+        self.updaters = updaters;
     }
 }
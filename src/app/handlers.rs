@@ -1,4 +1,7 @@
 pub mod assets;
+pub mod health;
+pub mod installers;
+pub mod metrics;
 pub mod providers;
 pub mod releases;
 pub mod repos;
@@ -1,6 +1,56 @@
+use crate::app::paths::{AssetRef, RepoPath};
 use crate::app::{self, paths};
-use actix_web::{http, web, Error, HttpResponse};
-use futures::{future, Future};
+use crate::provider::{self, proxy::AssetStream};
+use crate::{Asset, Cache, CacheKey, Location, ReleaseSource, Target};
+use actix_files::NamedFile;
+use actix_web::dev::SizedStream;
+use actix_web::http::Uri;
+use actix_web::{error, http, web, Error, HttpRequest, HttpResponse, Responder};
+use futures::{
+    future::{self, Either},
+    Future, Stream,
+};
+use ring::digest;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug, Serialize)]
+pub(crate) struct AssetJson<'a> {
+    name: &'a str,
+    size: Option<u64>,
+    content_type: Option<&'a str>,
+    sha256: Option<&'a str>,
+    archive: Option<&'a str>,
+    download_uri: String,
+}
+
+impl<'a> AssetJson<'a> {
+    /// Describes the assets of a target, which are downloaded through this server below
+    /// `assets_url` rather than from the provider directly.
+    pub(crate) fn for_target(target: &'a Target, assets_url: &str) -> Vec<Self> {
+        let mut assets = target
+            .assets()
+            .map(|asset| AssetJson::new(asset, assets_url))
+            .collect::<Vec<_>>();
+        assets.sort_by(|a, b| a.name.cmp(b.name));
+
+        assets
+    }
+
+    fn new(asset: &'a Asset, assets_url: &str) -> Self {
+        AssetJson {
+            name: asset.name(),
+            size: asset.size(),
+            content_type: asset.content_type(),
+            sha256: asset.sha256(),
+            archive: asset.archive(),
+            download_uri: format!("{}/{}", assets_url, paths::encode_segment(asset.name())),
+        }
+    }
+}
 
 pub fn get_assets_txt(
     path: web::Path<paths::Assets>,
@@ -17,13 +67,304 @@ pub fn get_assets_txt(
     })
 }
 
+pub fn get_assets_json(
+    req: HttpRequest,
+    path: web::Path<paths::Assets>,
+    data: web::Data<app::Data>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    future::result(paths::base_url(&req).and_then(|base_url| {
+        let target = paths::get_target(path.as_ref(), &data)?;
+        let assets_url = paths::assets_url(
+            &base_url,
+            path.as_ref(),
+            target.as_owner().name(),
+            target.name(),
+        );
+
+        Ok(HttpResponse::Ok().json(AssetJson::for_target(&target, &assets_url)))
+    }))
+}
+
 pub fn get_asset(
+    req: HttpRequest,
     path: web::Path<paths::Asset>,
     data: web::Data<app::Data>,
 ) -> impl Future<Item = HttpResponse, Error = Error> {
-    future::result(paths::get_asset(path.as_ref(), &data)).and_then(|asset| {
-        HttpResponse::Found()
-            .header(http::header::LOCATION, asset.download_uri().to_string())
-            .finish()
-    })
+    future::result(paths::get_asset(path.as_ref(), &data).and_then(
+        |asset| match asset.location() {
+            Location::Uri(uri) => {
+                let provider = paths::get_provider(path.as_ref(), &data)?;
+                if let Some(cache) = data.cache() {
+                    let key = cache_key(&*provider, path.as_ref(), &asset);
+                    if let Some(cached_path) = cache.get(&key) {
+                        return Ok(Either::B(future::result(
+                            NamedFile::open(cached_path)?.respond_to(&req),
+                        )));
+                    }
+
+                    return Ok(Either::A(Either::A(cache_asset(
+                        cache,
+                        key,
+                        &*provider,
+                        &asset,
+                        req.clone(),
+                    ))));
+                }
+                if paths::get_repo(path.as_ref(), &data)?.proxy() {
+                    return Ok(Either::A(Either::B(proxy_asset(&*provider, &asset, &req))));
+                }
+
+                Ok(Either::B(future::result(redirect_asset(&*provider, uri))))
+            }
+            Location::Path(path) => Ok(Either::B(future::result(
+                NamedFile::open(path)?.respond_to(&req),
+            ))),
+        },
+    ))
+    .flatten()
+}
+
+/// Responds with the SHA-256 digest of an asset, in the format read by `sha256sum --check`.
+pub fn get_asset_sha256(
+    path: web::Path<paths::Asset>,
+    data: web::Data<app::Data>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    future::result(paths::get_asset(path.as_ref(), &data).and_then(|asset| {
+        let sha256 = asset
+            .sha256()
+            .ok_or_else(|| error::ErrorNotFound(format!("checksum not found: {}", asset.name())))?;
+
+        Ok(HttpResponse::Ok().content_type("text/plain").body(format!(
+            "{}  {}\n",
+            sha256,
+            asset.name()
+        )))
+    }))
+}
+
+#[derive(Debug, Serialize)]
+struct VerifyJson {
+    asset: String,
+    expected: String,
+    actual: String,
+    verified: bool,
+}
+
+/// Downloads an asset server side and reports whether its digest matches the published one.
+///
+/// Cached assets are verified from the cache, so a corrupt cache entry is detected too. Remote
+/// assets which the provider cannot fetch itself and which are not cached cannot be verified.
+pub fn verify_asset(
+    path: web::Path<paths::Asset>,
+    data: web::Data<app::Data>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    future::result(paths::get_asset(path.as_ref(), &data).and_then(|asset| {
+        let expected = asset
+            .sha256()
+            .ok_or_else(|| error::ErrorNotFound(format!("checksum not found: {}", asset.name())))?
+            .to_string();
+        let name = asset.name().to_string();
+        let not_available = name.clone();
+
+        let provider = paths::get_provider(path.as_ref(), &data)?;
+        let local_path = match asset.location() {
+            Location::Path(file_path) => Some(file_path.clone()),
+            Location::Uri(_) => data
+                .cache()
+                .and_then(|cache| cache.get(&cache_key(&*provider, path.as_ref(), &asset))),
+        };
+        let actual = match local_path {
+            Some(local_path) => Either::A(
+                web::block(move || sha256_file(&local_path))
+                    .map_err(error::ErrorInternalServerError),
+            ),
+            None => Either::B(
+                provider
+                    .fetch_asset(&asset, None)
+                    .map_err(move |err| match err {
+                        provider::Error::ProxyUnsupported => error::ErrorConflict(format!(
+                            "asset not locally available: {}",
+                            not_available
+                        )),
+                        err => proxy_error(err),
+                    })
+                    .and_then(|stream| {
+                        stream.into_body().map_err(proxy_error).fold(
+                            digest::Context::new(&digest::SHA256),
+                            |mut ctx, chunk| {
+                                ctx.update(&chunk);
+                                Ok::<_, Error>(ctx)
+                            },
+                        )
+                    })
+                    .map(|ctx| hex_digest(ctx.finish())),
+            ),
+        };
+
+        Ok(actual.map(move |actual| {
+            HttpResponse::Ok().json(VerifyJson {
+                verified: actual == expected,
+                asset: name,
+                expected,
+                actual,
+            })
+        }))
+    }))
+    .flatten()
+}
+
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut ctx = digest::Context::new(&digest::SHA256);
+    let mut buf = [0; 8192];
+    loop {
+        match file.read(&mut buf)? {
+            0 => break,
+            len => ctx.update(&buf[..len]),
+        }
+    }
+
+    Ok(hex_digest(ctx.finish()))
+}
+
+fn hex_digest(digest: digest::Digest) -> String {
+    digest
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn cache_key<P: RepoPath>(provider: &dyn ReleaseSource, path: &P, asset: &AssetRef) -> CacheKey {
+    let release_id = asset.as_owner().as_owner().id();
+    // Release ids are derived from the tag for some providers, so the asset's revision keeps a
+    // replaced asset from being served stale. Without one, the download URI stands in for it.
+    let revision = match (asset.revision(), asset.download_uri()) {
+        (Some(revision), _) => revision.to_string(),
+        (None, Some(uri)) => uri.to_string(),
+        (None, None) => String::new(),
+    };
+
+    CacheKey::new(
+        provider.domain(),
+        path.owner(),
+        path.repo(),
+        release_id,
+        &revision,
+        asset.name(),
+    )
+}
+
+/// Fetches the bytes of a remote asset into the cache and serves them from there.
+///
+/// Assets which the provider cannot fetch itself are redirected to, as without a cache.
+fn cache_asset(
+    cache: Arc<Cache>,
+    key: CacheKey,
+    provider: &dyn ReleaseSource,
+    asset: &Asset,
+    req: HttpRequest,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    let (temp_path, file) = match cache.temp_file() {
+        Ok(temp) => temp,
+        Err(err) => return Either::A(future::err(error::ErrorInternalServerError(err))),
+    };
+    let cleanup_path = temp_path.clone();
+    let redirect = match asset.download_uri() {
+        Some(uri) => redirect_asset(provider, uri),
+        None => Err(proxy_error(provider::Error::ProxyUnsupported)),
+    };
+
+    Either::B(
+        provider
+            .fetch_asset(asset, None)
+            .then(move |result| match result {
+                Ok(stream) => Either::A(write_asset(stream, file).and_then(move |_| {
+                    let (path, cached) = cache.commit(&key, &temp_path)?;
+                    let named_file = NamedFile::open(&path)?;
+                    if !cached {
+                        fs::remove_file(&path)?;
+                    }
+
+                    named_file.respond_to(&req)
+                })),
+                Err(provider::Error::ProxyUnsupported) => {
+                    let _ = fs::remove_file(&temp_path);
+                    Either::B(future::result(redirect))
+                }
+                Err(err) => Either::B(future::err(proxy_error(err))),
+            })
+            .map_err(move |err| {
+                let _ = fs::remove_file(&cleanup_path);
+                err
+            }),
+    )
+}
+
+/// Writes the bytes of an asset stream to a file and syncs it, off the event loop.
+fn write_asset(stream: AssetStream, file: File) -> impl Future<Item = (), Error = Error> {
+    stream
+        .into_body()
+        .map_err(proxy_error)
+        .fold(file, |file, chunk| {
+            web::block(move || {
+                let mut file = file;
+                file.write_all(&chunk).map(|_| file)
+            })
+            .map_err(error::ErrorInternalServerError)
+        })
+        .and_then(|file| {
+            web::block(move || file.sync_all()).map_err(error::ErrorInternalServerError)
+        })
+}
+
+/// Redirects to the URI which the provider serves a remote asset from.
+fn redirect_asset(provider: &dyn ReleaseSource, uri: &Uri) -> Result<HttpResponse, Error> {
+    let uri = provider
+        .download_uri(uri)
+        .map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Found()
+        .header(http::header::LOCATION, uri.to_string())
+        .finish())
+}
+
+/// Streams the bytes of a remote asset through the provider's client, passing on the `Range`
+/// header of the request.
+fn proxy_asset(
+    provider: &dyn ReleaseSource,
+    asset: &Asset,
+    req: &HttpRequest,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    let range = req
+        .headers()
+        .get(http::header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map(ToString::to_string);
+
+    provider
+        .fetch_asset(asset, range)
+        .map_err(proxy_error)
+        .map(|stream| {
+            let mut response = HttpResponse::build(stream.status());
+            for (name, value) in stream.headers() {
+                response.header(name.clone(), value.clone());
+            }
+
+            match stream.content_length() {
+                Some(len) => response.body(SizedStream::new(
+                    len,
+                    stream.into_body().map_err(proxy_error),
+                )),
+                None => response.streaming(stream.into_body().map_err(proxy_error)),
+            }
+        })
+}
+
+fn proxy_error(err: provider::Error) -> Error {
+    match err {
+        provider::Error::ProxyUnsupported => error::ErrorNotImplemented(err),
+        provider::Error::RateLimited(_) => error::ErrorServiceUnavailable(err),
+        err => error::ErrorBadGateway(err),
+    }
 }
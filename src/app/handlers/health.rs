@@ -0,0 +1,106 @@
+use crate::app;
+use actix_web::{web, Error, HttpResponse};
+use futures::{future, Future};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Serialize)]
+struct ReadinessJson {
+    ready: bool,
+    repos: Vec<RepoReadinessJson>,
+}
+
+#[derive(Debug, Serialize)]
+struct RepoReadinessJson {
+    domain: String,
+    repo: String,
+    /// Whether the repo was updated successfully at least once.
+    populated: bool,
+    /// Whether the repo went more update intervals than allowed without a successful update.
+    stale: bool,
+    /// How long ago the repo was last updated successfully.
+    updated_secs_ago: Option<u64>,
+    /// How long ago the repo's most recently changed release was fetched.
+    release_updated_secs_ago: Option<u64>,
+}
+
+/// Returns whether the server is alive, which it is whenever it can answer.
+pub fn get_healthz() -> impl Future<Item = HttpResponse, Error = Error> {
+    future::ok(HttpResponse::Ok().content_type("text/plain").body("ok\n"))
+}
+
+/// Returns whether the server is ready to serve releases.
+///
+/// The server is ready once every repo was updated successfully. When `stale_intervals` is
+/// configured, it is no longer ready while any repo goes that many of its update intervals
+/// without a successful update, counted from when its updater was spawned if it never had one.
+pub fn get_readyz(data: web::Data<app::Data>) -> impl Future<Item = HttpResponse, Error = Error> {
+    let now = Instant::now();
+    let stale_intervals = data.stale_intervals();
+    let statuses = data
+        .updaters()
+        .statuses()
+        .into_iter()
+        .map(|status| {
+            (
+                (
+                    status.domain.clone(),
+                    status.owner.clone(),
+                    status.name.clone(),
+                ),
+                status,
+            )
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut repos = Vec::new();
+    for provider in data.providers() {
+        for repo in provider.repos() {
+            let key = (
+                provider.domain().to_string(),
+                repo.owner().to_string(),
+                repo.name().to_string(),
+            );
+            // A repo which was just added by a reload may not have an updater yet
+            let status = statuses.get(&key);
+            let updated = repo.updated();
+            let stale = match (stale_intervals, status) {
+                (Some(intervals), Some(status)) => {
+                    let since = updated.unwrap_or(status.spawned);
+                    now.duration_since(since) > repo.interval() * intervals.get()
+                }
+                _ => false,
+            };
+            let release_updated = repo
+                .releases()
+                .filter_map(|release| release.updated())
+                .max();
+
+            repos.push(RepoReadinessJson {
+                domain: key.0,
+                repo: format!("{}/{}", key.1, key.2),
+                populated: updated.is_some(),
+                stale,
+                updated_secs_ago: updated.map(|updated| secs_since(now, updated)),
+                release_updated_secs_ago: release_updated.map(|updated| secs_since(now, updated)),
+            });
+        }
+    }
+    repos.sort_by(|a, b| (&a.domain, &a.repo).cmp(&(&b.domain, &b.repo)));
+
+    let ready = repos.iter().all(|repo| repo.populated && !repo.stale);
+    let mut response = if ready {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::ServiceUnavailable()
+    };
+
+    future::ok(response.json(ReadinessJson { ready, repos }))
+}
+
+fn secs_since(now: Instant, then: Instant) -> u64 {
+    now.checked_duration_since(then)
+        .unwrap_or_else(|| Duration::from_secs(0))
+        .as_secs()
+}
@@ -0,0 +1,103 @@
+use crate::app::{self, paths};
+use crate::Repo;
+use actix_web::{error, web, Error, HttpRequest, HttpResponse};
+use futures::{future, Future};
+use serde::Deserialize;
+
+const INSTALL_SH: &str = include_str!("../templates/install.sh");
+const INSTALL_PS1: &str = include_str!("../templates/install.ps1");
+
+#[derive(Debug, Deserialize)]
+pub struct InstallerQuery {
+    /// The release which the installer installs unless told otherwise, `latest` by default.
+    version: Option<String>,
+    /// The asset which the installer installs, the repo's name by default.
+    asset: Option<String>,
+}
+
+pub fn get_install_sh(
+    req: HttpRequest,
+    path: web::Path<paths::Releases>,
+    query: web::Query<InstallerQuery>,
+    data: web::Data<app::Data>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    future::result(paths::get_repo(path.as_ref(), &data)).and_then(move |repo| {
+        let template = repo.install_sh().unwrap_or(INSTALL_SH);
+        render(template, &req, path.as_ref(), &repo, &query).map(|script| {
+            HttpResponse::Ok()
+                .content_type("text/plain; charset=utf-8")
+                .body(script)
+        })
+    })
+}
+
+pub fn get_install_ps1(
+    req: HttpRequest,
+    path: web::Path<paths::Releases>,
+    query: web::Query<InstallerQuery>,
+    data: web::Data<app::Data>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    future::result(paths::get_repo(path.as_ref(), &data)).and_then(move |repo| {
+        let template = repo.install_ps1().unwrap_or(INSTALL_PS1);
+        render(template, &req, path.as_ref(), &repo, &query).map(|script| {
+            HttpResponse::Ok()
+                .content_type("text/plain; charset=utf-8")
+                .body(script)
+        })
+    })
+}
+
+/// Renders an installer template, replacing each `{{name}}` placeholder with its value.
+///
+/// Values end up inside quoted strings of the script, so any value which could break out of its
+/// string is refused rather than escaped for each kind of script.
+fn render(
+    template: &str,
+    req: &HttpRequest,
+    path: &paths::Releases,
+    repo: &Repo,
+    query: &InstallerQuery,
+) -> Result<String, Error> {
+    let base_url = paths::base_url(req)?;
+    let version = query.version.as_deref().unwrap_or("latest");
+    let asset = query.asset.as_deref().unwrap_or(repo.name());
+
+    let vars = [
+        ("provider", path.provider.as_str()),
+        ("owner", repo.owner()),
+        ("repo", repo.name()),
+        ("version", version),
+        ("asset", asset),
+    ];
+    for (name, value) in &vars {
+        if !is_script_safe(value) {
+            return Err(error::ErrorBadRequest(format!(
+                "invalid installer {}: {}",
+                name, value
+            )));
+        }
+    }
+
+    let repo_url = format!(
+        "{}/v1/providers/{}/repos/{}/{}",
+        base_url,
+        path.provider,
+        repo.owner(),
+        repo.name()
+    );
+    let mut script = template.replace("{{repo_url}}", &repo_url);
+    for (name, value) in &vars {
+        script = script.replace(&format!("{{{{{}}}}}", name), value);
+    }
+
+    Ok(script)
+}
+
+fn is_script_safe(value: &str) -> bool {
+    !value.is_empty()
+        && !value.starts_with('.')
+        && value.chars().all(|c| {
+            c.is_ascii_alphanumeric()
+                || matches!(c, '.' | '_' | '-' | '+' | '~' | '^' | '=' | '*' | ',')
+        })
+}
@@ -0,0 +1,127 @@
+use crate::app;
+use crate::metrics::{self, Family};
+use actix_web::http::StatusCode;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use futures::{future, Future};
+
+/// The route label of requests which matched no route, so that arbitrary paths do not each get
+/// their own series.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Returns the metrics of the process in the Prometheus text format.
+///
+/// Besides the recorded counters, this reports the size of every repo's index and the rate limit
+/// budget of every provider which has one, as they are when scraped.
+pub fn get_metrics(data: web::Data<app::Data>) -> impl Future<Item = HttpResponse, Error = Error> {
+    let mut out = String::new();
+    metrics::global().render(&mut out);
+
+    let providers = data.providers();
+    let counts = providers
+        .iter()
+        .flat_map(|provider| {
+            provider.repos().map(move |repo| {
+                let releases = repo.releases().count();
+                let targets = repo
+                    .releases()
+                    .map(|rel| rel.targets().count())
+                    .sum::<usize>();
+                let assets = repo
+                    .releases()
+                    .flat_map(|rel| rel.targets())
+                    .map(|target| target.assets().count())
+                    .sum::<usize>();
+
+                (
+                    provider.domain(),
+                    repo.to_string(),
+                    [releases, targets, assets],
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    let sizes = [
+        ("artifetch_releases", "Releases in a repo's index."),
+        (
+            "artifetch_targets",
+            "Targets of all releases in a repo's index.",
+        ),
+        (
+            "artifetch_assets",
+            "Assets of all releases in a repo's index.",
+        ),
+    ];
+    for (i, (name, help)) in sizes.iter().enumerate() {
+        let mut family = Family::new(&mut out, name, "gauge", help);
+        for (domain, repo, values) in &counts {
+            family.sample(&[("domain", domain), ("repo", repo)], values[i] as f64);
+        }
+    }
+
+    let budgets = providers
+        .iter()
+        .filter_map(|provider| Some((provider.domain(), provider.rate_limit()?)))
+        .collect::<Vec<_>>();
+    let rate_limits = [
+        (
+            "artifetch_rate_limit_remaining",
+            "Requests left in a provider's current rate limit window.",
+        ),
+        (
+            "artifetch_rate_limit_limit",
+            "Requests allowed in each of a provider's rate limit windows.",
+        ),
+        (
+            "artifetch_rate_limit_reset_timestamp_seconds",
+            "When a provider's current rate limit window ends.",
+        ),
+    ];
+    for (i, (name, help)) in rate_limits.iter().enumerate() {
+        let mut family = Family::new(&mut out, name, "gauge", help);
+        for (domain, budget) in &budgets {
+            let value = [budget.remaining, budget.limit, budget.reset][i];
+            if let Some(value) = value {
+                family.sample(&[("domain", domain)], value as f64);
+            }
+        }
+    }
+
+    future::ok(
+        HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4")
+            .body(out),
+    )
+}
+
+/// Returns the route which a request matched, with its path parameters as `{name}` placeholders.
+pub fn route_label(req: &HttpRequest, status: StatusCode) -> String {
+    let path = req.path();
+    // Only a request which was not found can have missed every route. The resource map does not
+    // know the scopes' empty resources, so it is not asked about requests which were served.
+    if status == StatusCode::NOT_FOUND && !req.resource_map().has_resource(path) {
+        return UNMATCHED_ROUTE.to_string();
+    }
+
+    // Path parameters are matched in path order, so each one is found at or after the segment
+    // which matched the one before it
+    let mut params = req.match_info().iter().peekable();
+    path.split('/')
+        .map(|segment| match params.peek() {
+            Some((name, value)) if is_param_segment(segment, value) => {
+                let label = format!("{{{}}}{}", name, &segment[value.len()..]);
+                params.next();
+                label
+            }
+            _ => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Returns whether a path segment is a parameter's value, alone or followed by an extension such
+/// as the `.sha256` of `{asset}.sha256`.
+fn is_param_segment(segment: &str, value: &str) -> bool {
+    !value.is_empty()
+        && segment.starts_with(value)
+        && (segment.len() == value.len() || segment[value.len()..].starts_with('.'))
+}
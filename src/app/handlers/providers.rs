@@ -1,6 +1,28 @@
-use crate::app;
+use crate::app::{self, paths};
+use crate::provider::rate_limit::Budget;
 use actix_web::{web, Error, HttpResponse};
 use futures::{future, Future};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+struct ProviderJson<'a> {
+    domain: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct ProviderStatusJson<'a> {
+    domain: &'a str,
+    repos: usize,
+    rate_limit: Option<RateLimitJson>,
+}
+
+#[derive(Debug, Serialize)]
+struct RateLimitJson {
+    #[serde(flatten)]
+    budget: Budget,
+    low: bool,
+    paused_until: Option<u64>,
+}
 
 pub fn get_providers_txt(
     data: web::Data<app::Data>,
@@ -8,9 +30,42 @@ pub fn get_providers_txt(
     future::ok(
         HttpResponse::Ok().content_type("text/plain").body(
             data.providers()
+                .iter()
                 .map(|p| format!("{}\n", p.domain()))
                 .collect::<Vec<_>>()
                 .join(""),
         ),
     )
 }
+
+pub fn get_providers_json(
+    data: web::Data<app::Data>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    future::ok(
+        HttpResponse::Ok().json(
+            data.providers()
+                .iter()
+                .map(|p| ProviderJson { domain: p.domain() })
+                .collect::<Vec<_>>(),
+        ),
+    )
+}
+
+/// Returns the status of a provider, including the budget of its API rate limit if it has one.
+pub fn get_provider_status_json(
+    path: web::Path<paths::Repos>,
+    data: web::Data<app::Data>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    match paths::get_provider(path.as_ref(), &data) {
+        Ok(provider) => future::ok(HttpResponse::Ok().json(ProviderStatusJson {
+            domain: provider.domain(),
+            repos: provider.repos().count(),
+            rate_limit: provider.rate_limit().map(|budget| RateLimitJson {
+                budget,
+                low: budget.is_low(),
+                paused_until: budget.blocked_until(),
+            }),
+        })),
+        Err(err) => future::err(err),
+    }
+}
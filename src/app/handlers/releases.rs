@@ -1,17 +1,68 @@
 use crate::app::{self, paths};
+use crate::{Release, ReleaseOrder};
 use actix_web::{web, Error, HttpResponse};
 use futures::{future, Future};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct ReleasesQuery {
+    #[serde(default)]
+    sort: ReleaseOrder,
+}
+
+#[derive(Debug, Serialize)]
+struct ReleaseJson<'a> {
+    id: u64,
+    name: &'a str,
+    version: Option<String>,
+    created: Option<String>,
+    prerelease: bool,
+    targets: Vec<&'a str>,
+}
+
+impl<'a> From<&'a Release> for ReleaseJson<'a> {
+    fn from(release: &'a Release) -> Self {
+        let mut targets = release.targets().map(|t| t.name()).collect::<Vec<_>>();
+        targets.sort();
+
+        ReleaseJson {
+            id: release.id(),
+            name: release.name(),
+            version: release.version().map(ToString::to_string),
+            created: release.created().map(|created| created.to_rfc3339()),
+            prerelease: release.prerelease(),
+            targets,
+        }
+    }
+}
 
 pub fn get_releases_txt(
     path: web::Path<paths::Releases>,
+    query: web::Query<ReleasesQuery>,
     data: web::Data<app::Data>,
 ) -> impl Future<Item = HttpResponse, Error = Error> {
-    future::result(paths::get_repo(path.as_ref(), &data)).and_then(|repo| {
+    future::result(paths::get_repo(path.as_ref(), &data)).and_then(move |repo| {
         HttpResponse::Ok().content_type("text/plain").body(
-            repo.releases()
+            repo.releases_by(query.sort)
+                .into_iter()
                 .map(|r| format!("{}\n", r.name()))
                 .collect::<Vec<_>>()
                 .join(""),
         )
     })
 }
+
+pub fn get_releases_json(
+    path: web::Path<paths::Releases>,
+    query: web::Query<ReleasesQuery>,
+    data: web::Data<app::Data>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    future::result(paths::get_repo(path.as_ref(), &data)).and_then(move |repo| {
+        HttpResponse::Ok().json(
+            repo.releases_by(query.sort)
+                .into_iter()
+                .map(ReleaseJson::from)
+                .collect::<Vec<_>>(),
+        )
+    })
+}
@@ -1,6 +1,15 @@
 use crate::app::{self, paths};
 use actix_web::{web, Error, HttpResponse};
 use futures::{future, Future};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+struct RepoJson {
+    owner: String,
+    name: String,
+    latest_release: Option<String>,
+    latest_prerelease: Option<String>,
+}
 
 pub fn get_repos_txt(
     path: web::Path<paths::Repos>,
@@ -19,3 +28,25 @@ pub fn get_repos_txt(
         Err(err) => future::err(err),
     }
 }
+
+pub fn get_repos_json(
+    path: web::Path<paths::Repos>,
+    data: web::Data<app::Data>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    match paths::get_provider(path.as_ref(), &data) {
+        Ok(provider) => future::ok(
+            HttpResponse::Ok().json(
+                provider
+                    .repos()
+                    .map(|r| RepoJson {
+                        owner: r.owner().to_string(),
+                        name: r.name().to_string(),
+                        latest_release: r.latest_release().map(|rel| rel.name().to_string()),
+                        latest_prerelease: r.latest_prerelease().map(|rel| rel.name().to_string()),
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+        ),
+        Err(err) => future::err(err),
+    }
+}
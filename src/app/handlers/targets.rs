@@ -1,18 +1,53 @@
+use super::assets::AssetJson;
 use crate::app::{self, paths};
-use actix_web::{web, Error, HttpResponse};
+use actix_web::{web, Error, HttpRequest, HttpResponse};
 use futures::{future, Future};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+struct TargetJson<'a> {
+    name: &'a str,
+    aliases: &'a [String],
+    assets: Vec<AssetJson<'a>>,
+}
 
 pub fn get_targets_txt(
     path: web::Path<paths::Targets>,
     data: web::Data<app::Data>,
 ) -> impl Future<Item = HttpResponse, Error = Error> {
     future::result(paths::get_release(path.as_ref(), &data)).and_then(|release| {
-        HttpResponse::Ok().content_type("text/plain").body(
-            release
-                .targets()
-                .map(|t| format!("{}\n", t))
-                .collect::<Vec<_>>()
-                .join(""),
-        )
+        let mut targets = release
+            .targets()
+            .map(|t| format!("{}\n", t))
+            .collect::<Vec<_>>();
+        targets.sort();
+
+        HttpResponse::Ok()
+            .content_type("text/plain")
+            .body(targets.join(""))
     })
 }
+
+pub fn get_targets_json(
+    req: HttpRequest,
+    path: web::Path<paths::Targets>,
+    data: web::Data<app::Data>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    future::result(paths::base_url(&req).and_then(|base_url| {
+        let release = paths::get_release(path.as_ref(), &data)?;
+        let mut targets = release
+            .targets()
+            .map(|t| TargetJson {
+                name: t.name(),
+                aliases: t.aliases(),
+                assets: AssetJson::for_target(
+                    t,
+                    &paths::assets_url(&base_url, path.as_ref(), release.name(), t.name()),
+                ),
+            })
+            .collect::<Vec<_>>();
+        targets.sort_by(|a, b| a.name.cmp(b.name));
+
+        Ok(HttpResponse::Ok().json(targets))
+    }))
+}
@@ -1,6 +1,7 @@
 use crate::app;
-use actix_web::{error, web, Error};
+use actix_web::{error, web, Error, HttpRequest};
 use owning_ref::OwningRef;
+use semver::VersionReq;
 use serde::{de, Deserialize, Deserializer};
 use std::fmt;
 use std::io;
@@ -10,16 +11,50 @@ use std::sync::Arc;
 #[derive(Debug)]
 pub enum Version {
     Latest,
-    Version(String),
+    LatestPrerelease,
+    Requirement(String, VersionReq),
+    Exact(String),
+}
+
+/// Removes `v` prefixes from the versions of a requirement, so that `^v1.4` matches like `^1.4`.
+fn strip_v_prefixes(req: &str) -> String {
+    let mut stripped = String::with_capacity(req.len());
+    let mut chars = req.chars().peekable();
+    let mut prev = None::<char>;
+    while let Some(c) = chars.next() {
+        let starts_version = match prev {
+            None => true,
+            Some(p) => p.is_whitespace() || "^~=<>,".contains(p),
+        };
+        if c == 'v' && starts_version && chars.peek().is_some_and(char::is_ascii_digit) {
+            prev = Some(c);
+            continue;
+        }
+        stripped.push(c);
+        prev = Some(c);
+    }
+
+    stripped
 }
 
 impl FromStr for Version {
     type Err = io::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const REQ_OPERATORS: &[char] = &['^', '~', '=', '>', '<', '*'];
+
         match s {
             "latest" => Ok(Version::Latest),
-            ver => Ok(Version::Version(ver.to_string())),
+            "latest-prerelease" => Ok(Version::LatestPrerelease),
+            req if req.starts_with(REQ_OPERATORS) => VersionReq::parse(&strip_v_prefixes(req))
+                .map(|parsed| Version::Requirement(req.to_string(), parsed))
+                .map_err(|err| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("invalid version requirement {}: {}", req, err),
+                    )
+                }),
+            ver => Ok(Version::Exact(ver.to_string())),
         }
     }
 }
@@ -28,7 +63,9 @@ impl fmt::Display for Version {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Version::Latest => write!(f, "latest"),
-            Version::Version(version) => write!(f, "{}", version),
+            Version::LatestPrerelease => write!(f, "latest-prerelease"),
+            Version::Requirement(req, _) => write!(f, "{}", req),
+            Version::Exact(version) => write!(f, "{}", version),
         }
     }
 }
@@ -213,10 +250,10 @@ pub type ReleaseRef = OwningRef<Arc<crate::Repo>, crate::Release>;
 pub type TargetRef = OwningRef<ReleaseRef, crate::Target>;
 pub type AssetRef = OwningRef<TargetRef, crate::Asset>;
 
-pub fn get_provider<'a, P>(
+pub fn get_provider<P>(
     path: &P,
-    data: &'a web::Data<app::Data>,
-) -> Result<&'a crate::Provider, Error>
+    data: &web::Data<app::Data>,
+) -> Result<Arc<dyn crate::ReleaseSource>, Error>
 where
     P: ProviderPath,
 {
@@ -239,12 +276,16 @@ pub fn get_release<P>(path: &P, data: &web::Data<app::Data>) -> Result<ReleaseRe
 where
     P: ReleasePath + RepoPath,
 {
-    OwningRef::new(get_repo(path, data)?).try_map(|repo| {
-        match path.version() {
-            Version::Latest => repo.latest_release(),
-            Version::Version(version) => repo.release(version),
-        }
-        .ok_or_else(|| error::ErrorNotFound(format!("release not found: {}", path.version())))
+    let version = path.version();
+    let not_found = || error::ErrorNotFound(format!("release not found: {}", version));
+
+    OwningRef::new(get_repo(path, data)?).try_map(|repo| match version {
+        Version::Latest => repo.latest_release().ok_or_else(not_found),
+        Version::LatestPrerelease => repo.latest_prerelease().ok_or_else(not_found),
+        Version::Exact(name) => repo.release(name).ok_or_else(not_found),
+        Version::Requirement(req_str, req) => repo.release_matching(req).ok_or_else(|| {
+            error::ErrorNotFound(format!("no release matches requirement: {}", req_str))
+        }),
     })
 }
 
@@ -254,7 +295,7 @@ where
 {
     OwningRef::new(get_release(path, data)?).try_map(|release| {
         release
-            .target(path.target())
+            .resolve_target(path.target(), &data.target_aliases())
             .ok_or_else(|| error::ErrorNotFound(format!("target not found: {}", path.target())))
     })
 }
@@ -269,3 +310,116 @@ where
             .ok_or_else(|| error::ErrorNotFound(format!("asset not found: {}\n", path.asset())))
     })
 }
+
+/// Returns the scheme and host which a request was made to, such as `https://example.com`, for
+/// building URLs back to this server.
+///
+/// The host comes from the request's headers, so anything which could break out of a URL or a
+/// quoted string is refused.
+pub fn base_url(req: &HttpRequest) -> Result<String, Error> {
+    let info = req.connection_info();
+    let scheme = match info.scheme() {
+        scheme @ "http" | scheme @ "https" => scheme,
+        scheme => {
+            return Err(error::ErrorBadRequest(format!(
+                "unsupported scheme: {}",
+                scheme
+            )))
+        }
+    };
+    let host = info.host();
+    if !host
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-.:[]".contains(c))
+    {
+        return Err(error::ErrorBadRequest(format!("invalid host: {}", host)));
+    }
+
+    Ok(format!("{}://{}", scheme, host))
+}
+
+/// Returns the URL of the assets of a target, below which each asset is downloaded through this
+/// server.
+pub fn assets_url<P: RepoPath>(base_url: &str, path: &P, release: &str, target: &str) -> String {
+    format!(
+        "{}/v1/providers/{}/repos/{}/{}/releases/{}/targets/{}/assets",
+        base_url,
+        encode_segment(path.provider()),
+        encode_segment(path.owner()),
+        encode_segment(path.repo()),
+        encode_segment(release),
+        encode_segment(target)
+    )
+}
+
+/// Percent-encodes a path segment, leaving only unreserved characters as they are.
+pub fn encode_segment(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'~' => {
+                encoded.push(char::from(byte))
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requirement(s: &str) -> VersionReq {
+        match s.parse::<Version>().unwrap() {
+            Version::Requirement(req_str, req) => {
+                assert_eq!(req_str, s);
+                req
+            }
+            version => panic!("not a requirement: {:?}", version),
+        }
+    }
+
+    #[test]
+    fn parse_versions() {
+        assert!(matches!("latest".parse(), Ok(Version::Latest)));
+        assert!(matches!(
+            "latest-prerelease".parse(),
+            Ok(Version::LatestPrerelease)
+        ));
+        match "v1.2.3".parse() {
+            Ok(Version::Exact(version)) => assert_eq!(version, "v1.2.3"),
+            other => panic!("unexpected version: {:?}", other),
+        }
+        assert!("^not-a-version".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn parse_requirements() {
+        assert_eq!(requirement("^1"), VersionReq::parse("^1").unwrap());
+        assert_eq!(requirement("~1.2"), VersionReq::parse("~1.2").unwrap());
+        assert_eq!(
+            requirement(">=1.0, <2.0"),
+            VersionReq::parse(">=1.0, <2.0").unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_v_prefixed_requirements() {
+        assert_eq!(requirement("^v1.4"), VersionReq::parse("^1.4").unwrap());
+        assert_eq!(requirement("~v2.0.3"), VersionReq::parse("~2.0.3").unwrap());
+        assert_eq!(
+            requirement(">=v1.0, <v2"),
+            VersionReq::parse(">=1.0, <2").unwrap()
+        );
+    }
+
+    #[test]
+    fn strip_v_prefixes_of_versions_only() {
+        assert_eq!(strip_v_prefixes("^v1.4"), "^1.4");
+        assert_eq!(strip_v_prefixes(">=v1, <v2"), ">=1, <2");
+        assert_eq!(strip_v_prefixes("^1.0.0-dev.v2"), "^1.0.0-dev.v2");
+        assert_eq!(strip_v_prefixes("=1.0.0-rev1"), "=1.0.0-rev1");
+    }
+}
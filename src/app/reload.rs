@@ -0,0 +1,165 @@
+use super::config::{self, CacheConfig, Factories};
+use super::state::ReleaseSettings;
+use super::updater::Updaters;
+use super::Data;
+use crate::{Registry, Repo};
+use actix_web::web;
+use log::{error, info, warn};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// Reloads the config file while the server runs.
+///
+/// Registry entries and target aliases are replaced by those of the reloaded config. Repos which
+/// are kept keep their releases and entity tags, new repos are given an updater and the updaters
+/// of removed repos are stopped. The other settings are only read when the server starts, so
+/// changes to them are logged and otherwise ignored until a restart.
+#[derive(Debug)]
+pub struct Reloader {
+    factories: Factories,
+    updaters: Updaters,
+    path: PathBuf,
+    bind_addr: SocketAddr,
+    cache: Option<CacheConfig>,
+    state_file: Option<PathBuf>,
+}
+
+impl Reloader {
+    /// Returns a reloader for the file which a config was read from, if any.
+    pub fn new(factories: Factories, updaters: Updaters, config: &config::Config) -> Option<Self> {
+        Some(Reloader {
+            factories,
+            updaters,
+            path: config.path.clone()?,
+            bind_addr: config.bind_addr,
+            cache: config.cache.clone(),
+            state_file: config.state_file.clone(),
+        })
+    }
+
+    pub fn reload(&self, data: &web::Data<Data>) {
+        let config = match config::config(Some(&self.path)) {
+            Ok(config) => config,
+            Err(err) => {
+                error!(
+                    "failed to reload config, keeping the current config; path={}, err={}",
+                    self.path.display(),
+                    err
+                );
+                return;
+            }
+        };
+        if config.bind_addr != self.bind_addr {
+            warn!(
+                "bind_addr changed, restart to apply; bind_addr={}",
+                config.bind_addr
+            );
+        }
+        if config.cache != self.cache {
+            warn!("cache changed, restart to apply");
+        }
+        if config.state_file != self.state_file {
+            warn!("state_file changed, restart to apply");
+        }
+
+        let registry = match config::build_registry(config.registry, &self.factories) {
+            Ok(registry) => registry,
+            Err(err) => {
+                error!(
+                    "failed to build registry, keeping the current config; path={}, err={}",
+                    self.path.display(),
+                    err
+                );
+                return;
+            }
+        };
+        let kept = carry_over(&data.registry(), &registry);
+        data.set_registry(registry);
+        data.set_target_aliases(config::build_target_aliases(
+            config.target_aliases,
+            config.musl_fallback,
+        ));
+        data.set_stale_intervals(config.stale_intervals);
+
+        let removed = self.updaters.stop_removed(data);
+        let added = self.updaters.spawn_missing(data);
+        info!(
+            "reloaded config; path={}, kept={}, added={}, removed={}",
+            self.path.display(),
+            kept,
+            added,
+            removed
+        );
+    }
+}
+
+/// Restores the index of every repo which the next registry shares with the current one,
+/// returning the number of repos restored.
+///
+/// A repo is only shared when its registry entry keeps the same provider type. The entity tags of
+/// a repo are dropped when its release filter, target inference or pagination changed, so that
+/// its next update fetches the releases again rather than being told they were not modified.
+fn carry_over(current: &Registry, next: &Registry) -> usize {
+    let mut kept = 0;
+    for provider in next.iter() {
+        let current_provider = match current.get(provider.domain()) {
+            Some(current_provider) if current_provider.to_string() == provider.to_string() => {
+                current_provider
+            }
+            _ => continue,
+        };
+
+        for repo in provider.repos() {
+            let mut snapshot = match current_provider.repo(repo.owner(), repo.name()) {
+                Some(current_repo) => Repo::clone(&current_repo),
+                None => continue,
+            };
+            if ReleaseSettings::of(&snapshot, current_provider.pagination())
+                != ReleaseSettings::of(&repo, provider.pagination())
+            {
+                snapshot.set_releases_etag(None);
+                snapshot.set_latest_etag(None);
+            }
+
+            match provider.restore_repo(snapshot) {
+                Ok(_) => kept += 1,
+                Err(err) => warn!(
+                    "failed to keep repo index; domain={}, repo={}/{}, err={}",
+                    provider.domain(),
+                    repo.owner(),
+                    repo.name(),
+                    err
+                ),
+            }
+        }
+    }
+
+    kept
+}
+
+/// Spawns a task which reloads the config whenever the process receives `SIGHUP`.
+#[cfg(unix)]
+pub fn spawn_on_sighup(reloader: Reloader, data: web::Data<Data>) {
+    use futures::{Future, Stream};
+    use tokio_signal::unix::{Signal, SIGHUP};
+
+    actix_rt::spawn(
+        Signal::new(SIGHUP)
+            .flatten_stream()
+            .map_err(|err| {
+                error!("failed to listen for SIGHUP; err={}", err);
+            })
+            .for_each(move |_| {
+                info!("SIGHUP received, reloading config");
+                reloader.reload(&data);
+
+                Ok(())
+            }),
+    );
+}
+
+/// Reloading on `SIGHUP` is not supported on this platform.
+#[cfg(not(unix))]
+pub fn spawn_on_sighup(_reloader: Reloader, _data: web::Data<Data>) {
+    warn!("config reloading is only supported on unix platforms");
+}
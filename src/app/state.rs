@@ -0,0 +1,559 @@
+use super::Data;
+use crate::provider::Pagination;
+use crate::{Asset, Location, Release, Repo, Target};
+use actix_web::http::{uri::InvalidUri, HttpTryFrom, Uri};
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use futures::{
+    future::{self, Either},
+    Future, Stream,
+};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_timer::Interval;
+
+/// The version of the state file format, bumped whenever it changes incompatibly.
+const STATE_VERSION: u32 = 1;
+
+/// How often the index is checked for changes and saved.
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Deserialize, Serialize)]
+struct IndexState {
+    version: u32,
+    repos: Vec<RepoState>,
+}
+
+/// The settings of a repo which decide the releases its provider lists.
+///
+/// The entity tags of a repo are only kept while these stay the same, as otherwise its index may
+/// no longer match what the provider would return.
+#[derive(Debug, PartialEq)]
+pub(super) struct ReleaseSettings {
+    prereleases: bool,
+    drafts: bool,
+    target_inference: String,
+    per_page: Option<u32>,
+    max_releases: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct RepoState {
+    provider: String,
+    owner: String,
+    name: String,
+    prereleases: bool,
+    drafts: bool,
+    #[serde(default)]
+    target_inference: String,
+    #[serde(default)]
+    per_page: Option<u32>,
+    #[serde(default)]
+    max_releases: Option<usize>,
+    latest_release: Option<String>,
+    latest_prerelease: Option<String>,
+    releases_etag: Option<String>,
+    latest_etag: Option<String>,
+    releases: Vec<ReleaseState>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ReleaseState {
+    id: u64,
+    name: String,
+    prerelease: bool,
+    created: Option<String>,
+    targets: Vec<TargetState>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct TargetState {
+    name: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    assets: Vec<AssetState>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct AssetState {
+    name: String,
+    uri: Option<String>,
+    path: Option<PathBuf>,
+    size: Option<u64>,
+    content_type: Option<String>,
+    proxy_uri: Option<String>,
+    #[serde(default)]
+    sha256: Option<String>,
+    #[serde(default)]
+    archive: Option<String>,
+    #[serde(default)]
+    revision: Option<String>,
+}
+
+/// Restores the index of every configured repo from the state file, if there is one.
+///
+/// A missing, unreadable or outdated state file is logged and otherwise ignored, leaving repos to
+/// be populated by their updaters.
+pub fn restore(data: &Data, path: &Path) {
+    let state = match fs::read(path) {
+        Ok(bytes) => match serde_json::from_slice::<IndexState>(&bytes) {
+            Ok(state) => state,
+            Err(err) => {
+                warn!(
+                    "failed to parse state file, ignoring; path={}, err={}",
+                    path.display(),
+                    err
+                );
+                return;
+            }
+        },
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+            info!("no state file found; path={}", path.display());
+            return;
+        }
+        Err(err) => {
+            warn!(
+                "failed to read state file, ignoring; path={}, err={}",
+                path.display(),
+                err
+            );
+            return;
+        }
+    };
+    if state.version != STATE_VERSION {
+        warn!(
+            "state file has an unsupported version, ignoring; path={}, version={}",
+            path.display(),
+            state.version
+        );
+        return;
+    }
+
+    let mut restored = 0;
+    for repo_state in state.repos {
+        let provider = match data.provider(&repo_state.provider) {
+            Some(provider) => provider,
+            None => continue,
+        };
+        let repo = match provider.repo(&repo_state.owner, &repo_state.name) {
+            Some(repo) => repo,
+            None => continue,
+        };
+
+        let (domain, owner, name) = (
+            repo_state.provider.clone(),
+            repo_state.owner.clone(),
+            repo_state.name.clone(),
+        );
+        let snapshot = match repo_state.into_repo(&repo, provider.pagination()) {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                warn!(
+                    "failed to restore repo, skipping; domain={}, repo={}/{}, err={}",
+                    domain, owner, name, err
+                );
+                continue;
+            }
+        };
+        match provider.restore_repo(snapshot) {
+            Ok(_) => restored += 1,
+            Err(err) => warn!(
+                "failed to restore repo, skipping; domain={}, repo={}/{}, err={}",
+                domain, owner, name, err
+            ),
+        }
+    }
+
+    info!(
+        "restored index from state file; path={}, repos={}",
+        path.display(),
+        restored
+    );
+}
+
+/// Spawns a task which saves the index to the state file whenever it has changed.
+pub fn spawn_saver(data: web::Data<Data>, path: PathBuf) {
+    let last_saved = Arc::new(Mutex::new(None::<String>));
+
+    actix_rt::spawn(
+        Interval::new(Instant::now() + SAVE_INTERVAL, SAVE_INTERVAL)
+            .map_err(|err| {
+                error!("state timer errored; err={}", err);
+            })
+            .for_each(move |_| {
+                let json = match serde_json::to_string(&snapshot(&data)) {
+                    Ok(json) => json,
+                    Err(err) => {
+                        error!("failed to serialize index; err={}", err);
+                        return Either::A(future::ok(()));
+                    }
+                };
+                if last_saved.lock().expect("lock poisoned").as_ref() == Some(&json) {
+                    return Either::A(future::ok(()));
+                }
+
+                let path = path.clone();
+                let last_saved = last_saved.clone();
+                Either::B(
+                    web::block(move || write_state(&path, &json).map(|_| (path, json))).then(
+                        move |result| {
+                            match result {
+                                Ok((path, json)) => {
+                                    debug!("saved index to state file; path={}", path.display());
+                                    *last_saved.lock().expect("lock poisoned") = Some(json);
+                                }
+                                Err(err) => error!("failed to save state file; err={}", err),
+                            }
+
+                            Ok(())
+                        },
+                    ),
+                )
+            }),
+    );
+}
+
+/// Writes the state file through a temporary file, so that a partial write is never loaded.
+fn write_state(path: &Path, json: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, json)?;
+    fs::rename(&temp_path, path)
+}
+
+fn snapshot(data: &Data) -> IndexState {
+    let mut repos = data
+        .providers()
+        .into_iter()
+        .flat_map(|provider| {
+            provider
+                .repos()
+                .map(|repo| RepoState::from_repo(provider.domain(), provider.pagination(), &repo))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    repos.sort_by(|a, b| (&a.provider, &a.owner, &a.name).cmp(&(&b.provider, &b.owner, &b.name)));
+
+    IndexState {
+        version: STATE_VERSION,
+        repos,
+    }
+}
+
+impl ReleaseSettings {
+    pub(super) fn of(repo: &Repo, pagination: Option<Pagination>) -> Self {
+        ReleaseSettings {
+            prereleases: repo.prereleases(),
+            drafts: repo.drafts(),
+            target_inference: repo.target_inference().to_string(),
+            per_page: pagination.map(|p| p.per_page),
+            max_releases: pagination.and_then(|p| p.max_releases),
+        }
+    }
+}
+
+impl RepoState {
+    fn from_repo(provider: &str, pagination: Option<Pagination>, repo: &Repo) -> Self {
+        let settings = ReleaseSettings::of(repo, pagination);
+        RepoState {
+            provider: provider.to_string(),
+            owner: repo.owner().to_string(),
+            name: repo.name().to_string(),
+            prereleases: settings.prereleases,
+            drafts: settings.drafts,
+            target_inference: settings.target_inference,
+            per_page: settings.per_page,
+            max_releases: settings.max_releases,
+            latest_release: repo.latest_release().map(|r| r.name().to_string()),
+            latest_prerelease: repo.latest_prerelease().map(|r| r.name().to_string()),
+            releases_etag: repo.releases_etag().map(ToString::to_string),
+            latest_etag: repo.latest_etag().map(ToString::to_string),
+            releases: repo.releases().map(ReleaseState::from_release).collect(),
+        }
+    }
+
+    /// Builds a snapshot of a configured repo from its saved state.
+    ///
+    /// The entity tags are dropped when the repo's release filter, target inference or pagination
+    /// has changed since the state was saved, as the saved releases may no longer match what the
+    /// provider would return.
+    fn into_repo(
+        self,
+        configured: &Repo,
+        pagination: Option<Pagination>,
+    ) -> Result<Repo, InvalidUri> {
+        let saved = ReleaseSettings {
+            prereleases: self.prereleases,
+            drafts: self.drafts,
+            target_inference: self.target_inference,
+            per_page: self.per_page,
+            max_releases: self.max_releases,
+        };
+        let mut repo = Repo::new(self.owner, self.name);
+        repo.set_releases(
+            self.releases
+                .into_iter()
+                .map(ReleaseState::into_release)
+                .collect::<Result<Vec<_>, _>>()?,
+        );
+        repo.set_latest_release(self.latest_release);
+        repo.set_latest_prerelease(self.latest_prerelease);
+
+        if saved == ReleaseSettings::of(configured, pagination) {
+            repo.set_releases_etag(self.releases_etag.and_then(|etag| etag.parse().ok()));
+            repo.set_latest_etag(self.latest_etag.and_then(|etag| etag.parse().ok()));
+        }
+
+        Ok(repo)
+    }
+}
+
+impl ReleaseState {
+    fn from_release(release: &Release) -> Self {
+        let mut targets = release
+            .targets()
+            .map(TargetState::from_target)
+            .collect::<Vec<_>>();
+        targets.sort_by(|a, b| a.name.cmp(&b.name));
+
+        ReleaseState {
+            id: release.id(),
+            name: release.name().to_string(),
+            prerelease: release.prerelease(),
+            created: release.created().map(|created| created.to_rfc3339()),
+            targets,
+        }
+    }
+
+    fn into_release(self) -> Result<Release, InvalidUri> {
+        let mut release = Release::new(self.id, self.name);
+        release.set_prerelease(self.prerelease);
+        release.set_created(
+            self.created
+                .and_then(|created| DateTime::parse_from_rfc3339(&created).ok())
+                .map(|created| created.with_timezone(&Utc)),
+        );
+        release.set_targets(
+            self.targets
+                .into_iter()
+                .map(TargetState::into_target)
+                .collect::<Result<Vec<_>, _>>()?,
+        );
+
+        Ok(release)
+    }
+}
+
+impl TargetState {
+    fn from_target(target: &Target) -> Self {
+        let mut assets = target
+            .assets()
+            .map(AssetState::from_asset)
+            .collect::<Vec<_>>();
+        assets.sort_by(|a, b| a.name.cmp(&b.name));
+
+        TargetState {
+            name: target.name().to_string(),
+            aliases: target.aliases().to_vec(),
+            assets,
+        }
+    }
+
+    fn into_target(self) -> Result<Target, InvalidUri> {
+        let mut target = Target::new(self.name);
+        for alias in self.aliases {
+            target.push_alias(alias);
+        }
+        for asset in self.assets {
+            if let Some(asset) = asset.into_asset()? {
+                target.push_asset(asset);
+            }
+        }
+
+        Ok(target)
+    }
+}
+
+impl AssetState {
+    fn from_asset(asset: &Asset) -> Self {
+        let (uri, path) = match asset.location() {
+            Location::Uri(uri) => (Some(uri.to_string()), None),
+            Location::Path(path) => (None, Some(path.clone())),
+        };
+
+        AssetState {
+            name: asset.name().to_string(),
+            uri,
+            path,
+            size: asset.size(),
+            content_type: asset.content_type().map(ToString::to_string),
+            proxy_uri: asset.proxy_uri().map(ToString::to_string),
+            sha256: asset.sha256().map(ToString::to_string),
+            archive: asset.archive().map(ToString::to_string),
+            revision: asset.revision().map(ToString::to_string),
+        }
+    }
+
+    /// Converts the saved asset back, or returns `None` when it has no location.
+    fn into_asset(self) -> Result<Option<Asset>, InvalidUri> {
+        let location = match (self.uri, self.path) {
+            (Some(uri), _) => Location::Uri(Uri::try_from(uri.as_str())?),
+            (None, Some(path)) => Location::Path(path),
+            (None, None) => return Ok(None),
+        };
+
+        let mut asset = Asset::new(self.name, location);
+        asset.set_size(self.size);
+        asset.set_content_type(self.content_type);
+        asset.set_sha256(self.sha256);
+        asset.set_archive(self.archive);
+        asset.set_revision(self.revision);
+        asset.set_proxy_uri(
+            self.proxy_uri
+                .map(|uri| Uri::try_from(uri.as_str()))
+                .transpose()?,
+        );
+
+        Ok(Some(asset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::infer::TargetInference;
+    use chrono::TimeZone;
+
+    const SHA256: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    fn indexed_repo() -> Repo {
+        let mut asset = Asset::new(
+            "tool",
+            Uri::from_static("https://example.com/tool-linux.tar.gz"),
+        );
+        asset.set_size(Some(1024));
+        asset.set_content_type(Some("application/gzip"));
+        asset.set_proxy_uri(Some(Uri::from_static("https://api.example.com/assets/1")));
+        asset.set_sha256(Some(SHA256));
+        asset.set_archive(Some("tar.gz"));
+        asset.set_revision(Some("r1"));
+
+        let mut target = Target::new("x86_64-unknown-linux-gnu");
+        target.push_alias("linux");
+        target.push_asset(asset);
+        target.push_asset(Asset::new("docs", PathBuf::from("/srv/tool/docs.tar.gz")));
+
+        let mut release = Release::new(1, "v1.0.0");
+        release.set_created(Some(Utc.ymd(2019, 8, 1).and_hms(12, 0, 0)));
+        release.set_targets(vec![target]);
+        let mut prerelease = Release::new(2, "v1.1.0-rc.1");
+        prerelease.set_prerelease(true);
+
+        let mut repo = Repo::new("owner", "tool");
+        repo.set_prereleases(true);
+        repo.set_target_inference(TargetInference::Triples);
+        repo.set_releases(vec![release, prerelease]);
+        repo.set_latest_release(Some("v1.0.0"));
+        repo.set_latest_prerelease(Some("v1.1.0-rc.1"));
+        repo.set_releases_etag(Some("\"releases\"".parse().unwrap()));
+        repo.set_latest_etag(Some("\"latest\"".parse().unwrap()));
+
+        repo
+    }
+
+    fn round_trip(repo: &Repo, pagination: Option<Pagination>) -> RepoState {
+        let state = IndexState {
+            version: STATE_VERSION,
+            repos: vec![RepoState::from_repo("github.com", pagination, repo)],
+        };
+        let json = serde_json::to_string(&state).unwrap();
+        let mut state = serde_json::from_str::<IndexState>(&json).unwrap();
+        assert_eq!(state.version, STATE_VERSION);
+        assert_eq!(state.repos.len(), 1);
+
+        state.repos.remove(0)
+    }
+
+    #[test]
+    fn round_trip_index() {
+        let repo = indexed_repo();
+        let pagination = Some(Pagination::default());
+        let restored = round_trip(&repo, pagination)
+            .into_repo(&repo, pagination)
+            .unwrap();
+
+        assert_eq!(restored.owner(), "owner");
+        assert_eq!(restored.name(), "tool");
+        assert_eq!(restored.latest_release().unwrap().name(), "v1.0.0");
+        assert_eq!(restored.latest_prerelease().unwrap().name(), "v1.1.0-rc.1");
+        assert_eq!(restored.releases_etag().unwrap().as_str(), "\"releases\"");
+        assert_eq!(restored.latest_etag().unwrap().as_str(), "\"latest\"");
+
+        let release = restored.release("v1.0.0").unwrap();
+        assert_eq!(release.id(), 1);
+        assert!(!release.prerelease());
+        assert_eq!(release.created(), repo.release("v1.0.0").unwrap().created());
+        assert!(restored.release("v1.1.0-rc.1").unwrap().prerelease());
+
+        let target = release.target("x86_64-unknown-linux-gnu").unwrap();
+        assert_eq!(target.aliases(), &["linux".to_string()]);
+        let indexed_target = repo
+            .release("v1.0.0")
+            .unwrap()
+            .target("x86_64-unknown-linux-gnu")
+            .unwrap();
+        assert_eq!(target.asset("tool"), indexed_target.asset("tool"));
+        assert_eq!(target.asset("docs"), indexed_target.asset("docs"));
+    }
+
+    #[test]
+    fn drop_etags_when_settings_differ() {
+        let repo = indexed_repo();
+        let pagination = Some(Pagination::default());
+        let has_etags = |configured: &Repo, pagination: Option<Pagination>| {
+            let restored = round_trip(&repo, Some(Pagination::default()))
+                .into_repo(configured, pagination)
+                .unwrap();
+            assert!(restored.release("v1.0.0").is_some());
+            assert_eq!(
+                restored.releases_etag().is_some(),
+                restored.latest_etag().is_some()
+            );
+
+            restored.releases_etag().is_some()
+        };
+
+        assert!(has_etags(&repo, pagination));
+
+        let mut configured = indexed_repo();
+        configured.set_prereleases(false);
+        assert!(!has_etags(&configured, pagination));
+
+        let mut configured = indexed_repo();
+        configured.set_drafts(true);
+        assert!(!has_etags(&configured, pagination));
+
+        let mut configured = indexed_repo();
+        configured.set_target_inference(TargetInference::Off);
+        assert!(!has_etags(&configured, pagination));
+
+        let per_page = Pagination {
+            per_page: 50,
+            ..Pagination::default()
+        };
+        assert!(!has_etags(&repo, Some(per_page)));
+        let max_releases = Pagination {
+            max_releases: Some(10),
+            ..Pagination::default()
+        };
+        assert!(!has_etags(&repo, Some(max_releases)));
+        assert!(!has_etags(&repo, None));
+    }
+}
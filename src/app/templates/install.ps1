@@ -0,0 +1,118 @@
+# Installs {{asset}} from {{owner}}/{{repo}}.
+#
+# Usage:
+#
+#     irm {{repo_url}}/install.ps1 | iex
+#
+# Environment:
+#
+#     VERSION  the release to install (default: {{version}})
+#     TARGET   the target to install (default: detected from the processor architecture)
+#     PREFIX   the directory to install `bin\{{asset}}.exe` under
+#              (default: $env:LOCALAPPDATA\Programs\{{repo}})
+
+$ErrorActionPreference = 'Stop'
+$ProgressPreference = 'SilentlyContinue'
+
+$RepoUrl = '{{repo_url}}'
+$Asset = '{{asset}}'
+$Version = if ($env:VERSION) { $env:VERSION } else { '{{version}}' }
+$Prefix = if ($env:PREFIX) { $env:PREFIX } else { Join-Path $env:LOCALAPPDATA 'Programs\{{repo}}' }
+
+# Returns the `os-arch` pair of the host, which the server resolves to a target triple.
+function Get-HostTarget {
+    $arch = if ($env:PROCESSOR_ARCHITEW6432) { $env:PROCESSOR_ARCHITEW6432 } else { $env:PROCESSOR_ARCHITECTURE }
+    "windows-$($arch.ToLower())"
+}
+
+function Get-Text([string] $Url) {
+    $content = (Invoke-WebRequest -UseBasicParsing -Uri $Url).Content
+    if ($content -is [byte[]]) {
+        [Text.Encoding]::UTF8.GetString($content)
+    } else {
+        $content
+    }
+}
+
+function Get-Magic([string] $Path) {
+    $stream = [IO.File]::OpenRead($Path)
+    try {
+        $buffer = New-Object byte[] 4
+        $read = $stream.Read($buffer, 0, 4)
+        ($buffer[0..($read - 1)] | ForEach-Object { $_.ToString('x2') }) -join ''
+    } finally {
+        $stream.Close()
+    }
+}
+
+$Target = if ($env:TARGET) { $env:TARGET } else { Get-HostTarget }
+$ReleaseUrl = "$RepoUrl/releases/$Version"
+$TargetUrl = "$ReleaseUrl/targets/$Target"
+
+Write-Host "install: looking up $Asset $Version for $Target"
+try {
+    $assets = (Get-Text "$TargetUrl/assets.txt") -split "`n"
+} catch {
+    throw "release $Version has no target $Target"
+}
+if ($assets -notcontains $Asset) {
+    throw "target $Target of release $Version has no asset $Asset"
+}
+
+$tmp = Join-Path ([IO.Path]::GetTempPath()) ([IO.Path]::GetRandomFileName())
+New-Item -ItemType Directory -Path $tmp | Out-Null
+try {
+    $assetUrl = "$TargetUrl/assets/$Asset"
+    $download = Join-Path $tmp 'download'
+    Write-Host "install: downloading $assetUrl"
+    Invoke-WebRequest -UseBasicParsing -Uri $assetUrl -OutFile $download
+
+    $checksum = $null
+    try {
+        $checksum = ((Get-Text "$assetUrl.sha256") -split ' ')[0]
+    } catch {
+        Write-Host 'install: no checksum published, skipping verification'
+    }
+    if ($checksum) {
+        $actual = (Get-FileHash -Algorithm SHA256 -Path $download).Hash.ToLower()
+        if ($actual -ne $checksum) {
+            throw "checksum mismatch; expected=$checksum, actual=$actual"
+        }
+        Write-Host "install: verified checksum $actual"
+    }
+
+    # Tells the archive format apart by its leading bytes. Anything which is not a known archive
+    # is taken to be the executable itself.
+    $unpacked = Join-Path $tmp 'unpacked'
+    New-Item -ItemType Directory -Path $unpacked | Out-Null
+    $magic = Get-Magic $download
+    if ($magic.StartsWith('504b0304')) {
+        Move-Item $download "$download.zip"
+        Expand-Archive -Path "$download.zip" -DestinationPath $unpacked
+    } elseif ($magic.StartsWith('1f8b')) {
+        tar -xzf $download -C $unpacked
+        if ($LASTEXITCODE -ne 0) { throw 'failed to unpack the downloaded asset' }
+    } else {
+        Copy-Item $download (Join-Path $unpacked "$Asset.exe")
+    }
+
+    $bin = Get-ChildItem -Path $unpacked -Recurse -File |
+        Where-Object { $_.Name -eq "$Asset.exe" -or $_.Name -eq $Asset } |
+        Select-Object -First 1
+    if (-not $bin) {
+        throw "no $Asset executable found in the downloaded asset"
+    }
+
+    $binDir = Join-Path $Prefix 'bin'
+    New-Item -ItemType Directory -Force -Path $binDir | Out-Null
+    $dest = Join-Path $binDir "$Asset.exe"
+    Copy-Item $bin.FullName $dest -Force
+    Write-Host "install: installed $dest"
+
+    $userPath = [Environment]::GetEnvironmentVariable('Path', 'User')
+    if (($userPath -split ';') -notcontains $binDir) {
+        Write-Host "install: add $binDir to your Path to run $Asset"
+    }
+} finally {
+    Remove-Item -Recurse -Force $tmp
+}
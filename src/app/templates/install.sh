@@ -0,0 +1,162 @@
+#!/bin/sh
+# Installs {{asset}} from {{owner}}/{{repo}}.
+#
+# Usage:
+#
+#     curl -sSf {{repo_url}}/install.sh | sh
+#
+# Environment:
+#
+#     VERSION  the release to install (default: {{version}})
+#     TARGET   the target to install (default: detected from `uname -sm`)
+#     PREFIX   the directory to install `bin/{{asset}}` under (default: $HOME/.local)
+set -eu
+
+REPO_URL='{{repo_url}}'
+ASSET='{{asset}}'
+VERSION="${VERSION:-{{version}}}"
+PREFIX="${PREFIX:-$HOME/.local}"
+
+main() {
+    need_cmd uname
+    need_cmd mktemp
+    need_cmd grep
+    need_cmd od
+
+    target="${TARGET:-$(detect_target)}"
+    release_url="$REPO_URL/releases/$VERSION"
+    target_url="$release_url/targets/$target"
+
+    info "looking up $ASSET $VERSION for $target"
+    if ! assets="$(fetch "$target_url/assets.txt")"; then
+        err "release $VERSION has no target $target; available targets are:"
+        fetch "$release_url/targets.txt" >&2 || true
+        exit 1
+    fi
+    if ! printf '%s\n' "$assets" | grep -qx "$ASSET"; then
+        err "target $target of release $VERSION has no asset $ASSET"
+        exit 1
+    fi
+
+    tmp="$(mktemp -d)"
+    trap 'rm -rf "$tmp"' EXIT
+
+    asset_url="$target_url/assets/$ASSET"
+    info "downloading $asset_url"
+    download "$asset_url" "$tmp/download"
+
+    if checksum="$(fetch "$asset_url.sha256" 2>/dev/null)"; then
+        verify "$tmp/download" "${checksum%% *}"
+    else
+        info "no checksum published, skipping verification"
+    fi
+
+    mkdir "$tmp/unpacked"
+    unpack "$tmp/download" "$tmp/unpacked"
+    bin="$(find "$tmp/unpacked" -type f -name "$ASSET" | head -n 1)"
+    if [ -z "$bin" ]; then
+        err "no $ASSET executable found in the downloaded asset"
+        exit 1
+    fi
+
+    mkdir -p "$PREFIX/bin"
+    cp "$bin" "$PREFIX/bin/$ASSET"
+    chmod 755 "$PREFIX/bin/$ASSET"
+    info "installed $PREFIX/bin/$ASSET"
+
+    case ":$PATH:" in
+        *":$PREFIX/bin:"*) ;;
+        *) info "add $PREFIX/bin to your PATH to run $ASSET" ;;
+    esac
+}
+
+# Prints the `os-arch` pair of the host, which the server resolves to a target triple.
+detect_target() {
+    os="$(uname -s | tr '[:upper:]' '[:lower:]')"
+    arch="$(uname -m | tr '[:upper:]' '[:lower:]')"
+    case "$os" in
+        mingw* | msys* | cygwin*) os=windows ;;
+    esac
+
+    printf '%s-%s\n' "$os" "$arch"
+}
+
+fetch() {
+    if command -v curl >/dev/null 2>&1; then
+        curl -sSfL "$1"
+    elif command -v wget >/dev/null 2>&1; then
+        wget -qO- "$1"
+    else
+        err "curl or wget is required"
+        exit 1
+    fi
+}
+
+download() {
+    if command -v curl >/dev/null 2>&1; then
+        curl -sSfL -o "$2" "$1"
+    elif command -v wget >/dev/null 2>&1; then
+        wget -qO "$2" "$1"
+    else
+        err "curl or wget is required"
+        exit 1
+    fi
+}
+
+verify() {
+    if command -v sha256sum >/dev/null 2>&1; then
+        actual="$(sha256sum "$1" | cut -d ' ' -f 1)"
+    elif command -v shasum >/dev/null 2>&1; then
+        actual="$(shasum -a 256 "$1" | cut -d ' ' -f 1)"
+    elif command -v openssl >/dev/null 2>&1; then
+        actual="$(openssl dgst -sha256 "$1" | sed 's/^.* //')"
+    else
+        info "no sha256 tool found, skipping verification"
+        return
+    fi
+
+    if [ "$actual" != "$2" ]; then
+        err "checksum mismatch; expected=$2, actual=$actual"
+        exit 1
+    fi
+    info "verified checksum $actual"
+}
+
+# Unpacks an archive into a directory, telling its format apart by its leading bytes. Anything
+# which is not a known archive is taken to be the executable itself.
+unpack() {
+    magic="$(od -An -tx1 -N6 "$1" | tr -d ' \n')"
+    case "$magic" in
+        1f8b*) tar -xzf "$1" -C "$2" ;;
+        fd377a585a00) tar -xJf "$1" -C "$2" ;;
+        425a68*) tar -xjf "$1" -C "$2" ;;
+        504b0304*)
+            need_cmd unzip
+            unzip -q "$1" -d "$2"
+            ;;
+        *)
+            if [ "$(od -An -c -j257 -N5 "$1" | tr -d ' \n')" = "ustar" ]; then
+                tar -xf "$1" -C "$2"
+            else
+                cp "$1" "$2/$ASSET"
+            fi
+            ;;
+    esac
+}
+
+need_cmd() {
+    if ! command -v "$1" >/dev/null 2>&1; then
+        err "$1 is required"
+        exit 1
+    fi
+}
+
+info() {
+    printf 'install: %s\n' "$1" >&2
+}
+
+err() {
+    printf 'install: error: %s\n' "$1" >&2
+}
+
+main "$@"
@@ -1,54 +1,241 @@
 use super::Data;
-use crate::{provider, Repo};
+use crate::{metrics, provider, Repo};
 use actix_web::web;
-use futures::{Future, Stream};
+use futures::future::{self, Either, Loop};
+use futures::Future;
 use log::{error, info, warn};
 use rand::Rng;
+use std::collections::HashMap;
 use std::fmt;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tokio_timer::{Delay, Interval};
+use tokio_timer::Delay;
 
+/// The longest that the updates of a failing repo back off to, unless its interval is longer.
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// The domain, owner and name of a repo.
+type RepoKey = (String, String, String);
+
+/// The updaters which are running, keyed by their repo, so that the updater of a repo which is
+/// removed from the config can be stopped and readiness can wait for every repo to be populated.
+#[derive(Clone, Debug, Default)]
+pub struct Updaters(Arc<Mutex<HashMap<RepoKey, Handle>>>);
+
+/// The state which an updater shares with the rest of the server.
+#[derive(Clone, Debug)]
+struct Handle {
+    spawned: Instant,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Handle {
+    fn new() -> Self {
+        Handle {
+            spawned: Instant::now(),
+            stopped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+/// When the updater of a repo was spawned.
+#[derive(Debug)]
+pub struct UpdaterStatus {
+    pub domain: String,
+    pub owner: String,
+    pub name: String,
+    /// When the updater was spawned.
+    pub spawned: Instant,
+}
+
+impl Updaters {
+    /// Spawns an updater for every repo of every provider which does not have one yet, returning
+    /// the number spawned.
+    pub fn spawn_missing(&self, data: &web::Data<Data>) -> usize {
+        let mut updaters = self.0.lock().expect("lock poisoned");
+        let mut spawned = 0;
+        for provider in data.providers() {
+            for repo in provider.repos() {
+                let key = (
+                    provider.domain().to_string(),
+                    repo.owner().to_string(),
+                    repo.name().to_string(),
+                );
+                if updaters.contains_key(&key) {
+                    continue;
+                }
+
+                let updater = RepoUpdater::new(data.clone(), &key.0, &key.1, &key.2);
+                updaters.insert(key, updater.handle.clone());
+                spawn(updater);
+                spawned += 1;
+            }
+        }
+
+        spawned
+    }
+
+    /// Stops the updater of every repo which no longer exists, returning the number stopped.
+    pub fn stop_removed(&self, data: &web::Data<Data>) -> usize {
+        let mut updaters = self.0.lock().expect("lock poisoned");
+        let before = updaters.len();
+        updaters.retain(|(domain, owner, name), handle| {
+            let exists = data
+                .provider(domain)
+                .and_then(|provider| provider.repo(owner, name))
+                .is_some();
+            if !exists {
+                handle.stopped.store(true, Ordering::SeqCst);
+                metrics::global().remove_repo(domain, owner, name);
+            }
+
+            exists
+        });
+
+        before - updaters.len()
+    }
+
+    /// Returns the status of every running updater.
+    pub fn statuses(&self) -> Vec<UpdaterStatus> {
+        let updaters = self.0.lock().expect("lock poisoned");
+        updaters
+            .iter()
+            .map(|((domain, owner, name), handle)| UpdaterStatus {
+                domain: domain.clone(),
+                owner: owner.clone(),
+                name: name.clone(),
+                spawned: handle.spawned,
+            })
+            .collect()
+    }
+}
+
+/// Spawns the updates of a repo, populating it at once and then updating it every interval.
+///
+/// The first update after populating is delayed by a random splay of up to the repo's splay. After
+/// consecutive failed updates, the delay grows exponentially up to `MAX_BACKOFF`, with jitter so
+/// that repos which fail together are not retried together, and is reset by the next success.
+/// Updates are slowed while the provider's rate limit budget is low, and paused while it is
+/// exhausted, which does not count as a failure. Updates stop once the repo is removed.
 pub fn spawn(updater: RepoUpdater) {
     let updater = Arc::new(updater);
-    let initial_updater = updater.clone();
 
-    actix_rt::spawn(
-        Delay::new(Instant::now())
-            .map_err(|err| {
-                error!("initial timer errored; err={}", err);
-            })
-            .and_then(move |_| {
-                info!("populating repo; {}", &initial_updater);
-                let uerr = initial_updater.clone();
-                initial_updater.update().map_err(move |err| {
-                    error!("populate failed; {}, err={}", uerr, err);
-                })
-            }),
-    );
-
-    actix_rt::spawn(
-        Interval::new(
-            Instant::now() + rand_splay_delay() + updater.interval(),
-            updater.interval(),
-        )
-        .map_err(|err| {
-            error!("timer errored; err={}", err);
-        })
-        .for_each(move |_| {
-            info!("updating repo; {}", &updater);
-            let uerr = updater.clone();
-            updater.update().map_err(move |err| {
-                warn!("update failed; {}, err={}", uerr, err);
-            })
-        }),
-    );
+    actix_rt::spawn(future::loop_fn(
+        (updater, 0, true),
+        |(updater, failures, populating): (Arc<RepoUpdater>, u32, bool)| {
+            if updater.is_stopped() {
+                info!("stopped updating removed repo; {}", &updater);
+                return Either::A(future::ok(Loop::Break(())));
+            }
+
+            if populating {
+                info!("populating repo; {}", &updater);
+            } else {
+                info!("updating repo; {}", &updater);
+            }
+            metrics::global().update_started(&updater.domain, &updater.owner, &updater.name);
+            Either::B(updater.update().then(move |result| {
+                metrics::global().update_finished(
+                    &updater.domain,
+                    &updater.owner,
+                    &updater.name,
+                    result.is_ok(),
+                );
+                let failures = match result {
+                    Ok(_) => 0,
+                    Err(provider::Error::RateLimited(until)) => {
+                        warn!(
+                            "repo updates paused for rate limit; {}, until={}",
+                            &updater,
+                            until.to_rfc3339()
+                        );
+                        failures
+                    }
+                    Err(err) => {
+                        let failures = failures.saturating_add(1);
+                        if populating {
+                            error!(
+                                "populate failed; {}, failures={}, err={}",
+                                &updater, failures, err
+                            );
+                        } else {
+                            warn!(
+                                "update failed; {}, failures={}, err={}",
+                                &updater, failures, err
+                            );
+                        }
+                        failures
+                    }
+                };
+
+                let (interval, splay) = match updater.schedule() {
+                    Some(schedule) => schedule,
+                    None => {
+                        info!("stopped updating removed repo; {}", &updater);
+                        return Either::A(future::ok(Loop::Break(())));
+                    }
+                };
+                let mut delay = if failures > 0 {
+                    let delay = backoff_delay(interval, failures);
+                    info!(
+                        "backing off repo updates; {}, delay={}s",
+                        &updater,
+                        delay.as_secs()
+                    );
+                    delay
+                } else if populating {
+                    rand_splay_delay(splay) + interval
+                } else {
+                    interval
+                };
+                if let Some(throttle) = updater.throttle().filter(|&throttle| throttle > delay) {
+                    info!(
+                        "slowing repo updates for rate limit; {}, delay={}s",
+                        &updater,
+                        throttle.as_secs()
+                    );
+                    delay = throttle;
+                }
+
+                Either::B(
+                    Delay::new(Instant::now() + delay)
+                        .map(move |_| Loop::Continue((updater, failures, false)))
+                        .map_err(|err| {
+                            error!("timer errored; err={}", err);
+                        }),
+                )
+            }))
+        },
+    ));
 }
 
-fn rand_splay_delay() -> Duration {
+fn rand_splay_delay(splay: Duration) -> Duration {
     let mut rng = rand::thread_rng();
 
-    Duration::from_secs(rng.gen_range(0, 30))
+    Duration::from_millis(rng.gen_range(0, millis(splay) + 1))
+}
+
+/// Returns the delay before retrying a repo after `failures` consecutive failed updates.
+///
+/// The delay doubles with each failure, starting from twice the interval and capped at
+/// `MAX_BACKOFF` (or the interval, if that is longer). A random jitter of up to half the delay is
+/// taken off.
+fn backoff_delay(interval: Duration, failures: u32) -> Duration {
+    let max = MAX_BACKOFF.max(interval);
+    let delay = interval
+        .checked_mul(1 << failures.min(16))
+        .map_or(max, |delay| delay.min(max));
+    let jitter = rand::thread_rng().gen_range(0, millis(delay) / 2 + 1);
+
+    delay - Duration::from_millis(jitter)
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration
+        .as_secs()
+        .saturating_mul(1000)
+        .saturating_add(u64::from(duration.subsec_millis()))
 }
 
 #[derive(Clone, Debug)]
@@ -57,6 +244,7 @@ pub struct RepoUpdater {
     domain: String,
     owner: String,
     name: String,
+    handle: Handle,
 }
 
 impl RepoUpdater {
@@ -75,26 +263,40 @@ impl RepoUpdater {
             domain,
             owner,
             name,
+            handle: Handle::new(),
         }
     }
 
-    pub fn interval(&self) -> Duration {
-        self.repo().interval()
+    /// Returns the interval and splay of the repo's updates, or `None` once the repo is removed.
+    pub fn schedule(&self) -> Option<(Duration, Duration)> {
+        self.repo().map(|repo| (repo.interval(), repo.splay()))
+    }
+
+    /// Returns how long to wait before the next update so that the provider's rate limit budget
+    /// lasts, if it is low or exhausted.
+    pub fn throttle(&self) -> Option<Duration> {
+        let provider = self.data.provider(&self.domain)?;
+        let budget = provider.rate_limit()?;
+
+        budget.throttle(provider.repos().count())
+    }
+
+    /// Returns whether the updater was stopped, as its repo was removed.
+    pub fn is_stopped(&self) -> bool {
+        self.handle.stopped.load(Ordering::SeqCst)
     }
 
     pub fn update(&self) -> impl Future<Item = (), Error = provider::Error> {
-        self.data
-            .provider(&self.domain)
-            .expect("provider domain should exist")
-            .update_repo(self.owner.clone(), self.name.clone())
+        match self.data.provider(&self.domain) {
+            Some(provider) => Either::A(provider.update_repo(&self.owner, &self.name)),
+            None => Either::B(future::err(provider::Error::RepoNotFound)),
+        }
     }
 
-    fn repo(&self) -> Arc<Repo> {
+    fn repo(&self) -> Option<Arc<Repo>> {
         self.data
-            .provider(&self.domain)
-            .expect("provider domain should exist")
+            .provider(&self.domain)?
             .repo(&self.owner, &self.name)
-            .expect("repo should exist in provider")
     }
 }
 
@@ -107,3 +309,62 @@ impl fmt::Display for RepoUpdater {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that every backoff delay is within the jitter of the expected delay.
+    fn assert_backoff(interval: Duration, failures: u32, expected: Duration) {
+        for _ in 0..100 {
+            let delay = backoff_delay(interval, failures);
+            assert!(
+                delay <= expected && delay >= expected / 2,
+                "delay {:?} out of bounds for {:?}",
+                delay,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn backoff_doubles() {
+        let interval = Duration::from_secs(30);
+
+        assert_backoff(interval, 1, Duration::from_secs(60));
+        assert_backoff(interval, 2, Duration::from_secs(120));
+        assert_backoff(interval, 5, Duration::from_secs(960));
+    }
+
+    #[test]
+    fn backoff_capped() {
+        let interval = Duration::from_secs(30);
+
+        assert_backoff(interval, 7, MAX_BACKOFF);
+        assert_backoff(interval, 16, MAX_BACKOFF);
+        assert_backoff(interval, u32::MAX, MAX_BACKOFF);
+    }
+
+    #[test]
+    fn backoff_capped_at_long_interval() {
+        let interval = MAX_BACKOFF * 3;
+
+        assert_backoff(interval, 1, interval);
+        assert_backoff(interval, u32::MAX, interval);
+        assert_backoff(
+            Duration::from_secs(u64::MAX),
+            u32::MAX,
+            Duration::from_secs(u64::MAX),
+        );
+    }
+
+    #[test]
+    fn backoff_jitter_varies() {
+        let interval = Duration::from_secs(30);
+        let delays = (0..100)
+            .map(|_| backoff_delay(interval, 6))
+            .collect::<std::collections::HashSet<_>>();
+
+        assert!(delays.len() > 1);
+    }
+}
@@ -1,20 +1,33 @@
 use actix_web::http::Uri;
+use std::path::{Path, PathBuf};
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct Asset {
     name: String,
-    download_uri: Uri,
+    location: Location,
+    size: Option<u64>,
+    content_type: Option<String>,
+    proxy_uri: Option<Uri>,
+    sha256: Option<String>,
+    archive: Option<String>,
+    revision: Option<String>,
 }
 
 impl Asset {
-    pub fn new<S, U>(name: S, download_uri: U) -> Self
+    pub fn new<S, L>(name: S, location: L) -> Self
     where
         S: Into<String>,
-        U: Into<Uri>,
+        L: Into<Location>,
     {
         Asset {
             name: name.into(),
-            download_uri: download_uri.into(),
+            location: location.into(),
+            size: None,
+            content_type: None,
+            proxy_uri: None,
+            sha256: None,
+            archive: None,
+            revision: None,
         }
     }
 
@@ -22,7 +35,99 @@ impl Asset {
         &self.name
     }
 
-    pub fn download_uri(&self) -> &Uri {
-        &self.download_uri
+    pub fn location(&self) -> &Location {
+        &self.location
+    }
+
+    /// Returns the size of the asset in bytes, if the provider reports it.
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    pub fn set_size(&mut self, size: Option<u64>) {
+        self.size = size;
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    pub fn set_content_type<S: Into<String>>(&mut self, content_type: Option<S>) {
+        self.content_type = content_type.map(Into::into);
+    }
+
+    /// Returns the hex encoded SHA-256 digest of the asset, if it was published.
+    pub fn sha256(&self) -> Option<&str> {
+        self.sha256.as_deref()
+    }
+
+    pub fn set_sha256<S: Into<String>>(&mut self, sha256: Option<S>) {
+        self.sha256 = sha256.map(Into::into);
+    }
+
+    /// Returns the archive format of the asset, such as `tar.gz` or `zip`, if it was declared.
+    pub fn archive(&self) -> Option<&str> {
+        self.archive.as_deref()
+    }
+
+    pub fn set_archive<S: Into<String>>(&mut self, archive: Option<S>) {
+        self.archive = archive.map(Into::into);
+    }
+
+    /// Returns the provider's identifier for the current bytes of the asset, such as an ETag or
+    /// an update time, which changes when the asset is replaced.
+    pub fn revision(&self) -> Option<&str> {
+        self.revision.as_deref()
+    }
+
+    pub fn set_revision<S: Into<String>>(&mut self, revision: Option<S>) {
+        self.revision = revision.map(Into::into);
+    }
+
+    pub fn download_uri(&self) -> Option<&Uri> {
+        match self.location {
+            Location::Uri(ref uri) => Some(uri),
+            Location::Path(_) => None,
+        }
+    }
+
+    /// Returns the URI which the asset bytes are fetched from when proxying downloads.
+    ///
+    /// This is the download URI, unless the provider fetches assets from elsewhere through its
+    /// client, as GitHub does through its API.
+    pub fn proxy_uri(&self) -> Option<&Uri> {
+        self.proxy_uri.as_ref().or_else(|| self.download_uri())
+    }
+
+    pub fn set_proxy_uri(&mut self, uri: Option<Uri>) {
+        self.proxy_uri = uri;
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        match self.location {
+            Location::Uri(_) => None,
+            Location::Path(ref path) => Some(path),
+        }
+    }
+}
+
+/// Where the bytes of an asset can be found.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Location {
+    /// A remote asset which clients are redirected to.
+    Uri(Uri),
+    /// A local file which is served directly.
+    Path(PathBuf),
+}
+
+impl From<Uri> for Location {
+    fn from(uri: Uri) -> Self {
+        Location::Uri(uri)
+    }
+}
+
+impl From<PathBuf> for Location {
+    fn from(path: PathBuf) -> Self {
+        Location::Path(path)
     }
 }
@@ -0,0 +1,482 @@
+use log::{debug, info, warn};
+use rand::{distributions::Alphanumeric, Rng};
+use ring::digest;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// The directory below the configured cache path which holds the entries, so that the cache never
+/// touches other files when pointed at a shared directory.
+const CACHE_DIR: &str = "artifetch-v1";
+
+const TMP_DIR: &str = ".tmp";
+
+/// Identifies the bytes of an asset in the cache.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct CacheKey {
+    provider: String,
+    owner: String,
+    repo: String,
+    release_id: u64,
+    revision: String,
+    asset: String,
+}
+
+impl CacheKey {
+    /// Creates the key of an asset's bytes, where `revision` identifies the current bytes of the
+    /// asset so that a replaced asset gets a new entry.
+    pub fn new<P, O, R, A>(
+        provider: P,
+        owner: O,
+        repo: R,
+        release_id: u64,
+        revision: &str,
+        asset: A,
+    ) -> Self
+    where
+        P: Into<String>,
+        O: Into<String>,
+        R: Into<String>,
+        A: Into<String>,
+    {
+        let revision = digest::digest(&digest::SHA256, revision.as_bytes())
+            .as_ref()
+            .iter()
+            .take(8)
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        CacheKey {
+            provider: provider.into(),
+            owner: owner.into(),
+            repo: repo.into(),
+            release_id,
+            revision,
+            asset: asset.into(),
+        }
+    }
+
+    /// Returns the path of the entry, relative to the cache root.
+    fn rel_path(&self) -> PathBuf {
+        [
+            escape_component(&self.provider),
+            escape_component(&self.owner),
+            escape_component(&self.repo),
+            self.release_id.to_string(),
+            self.revision.clone(),
+            escape_component(&self.asset),
+        ]
+        .iter()
+        .collect()
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}/{}/{}/{}/{}",
+            self.provider, self.owner, self.repo, self.release_id, self.revision, self.asset
+        )
+    }
+}
+
+/// A size bounded cache of asset bytes on disk.
+///
+/// Entries are written to a temporary file first and moved into place once complete, so a partial
+/// download is never served. The least recently used entries are evicted once the total size of
+/// the cache exceeds its maximum. Using an entry also sets its modification time, so that entries
+/// on disk are picked up again in the same order when the cache is reopened.
+///
+/// Entries are kept in an `artifetch-v1` directory below the configured path, and only files in
+/// that directory are ever indexed or removed.
+pub struct Cache {
+    root: PathBuf,
+    max_size: u64,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<PathBuf, CacheEntry>,
+    size: u64,
+    clock: u64,
+}
+
+struct CacheEntry {
+    size: u64,
+    last_used: u64,
+}
+
+impl Cache {
+    /// Opens the cache below `path`, creating it if needed and indexing any existing entries.
+    pub fn open<P: Into<PathBuf>>(path: P, max_size: u64) -> io::Result<Self> {
+        let root = path.into().join(CACHE_DIR);
+        let tmp_dir = root.join(TMP_DIR);
+        if tmp_dir.is_dir() {
+            fs::remove_dir_all(&tmp_dir)?;
+        }
+        fs::create_dir_all(&tmp_dir)?;
+
+        let mut files = Vec::new();
+        scan_entries(&root, &root, &mut files)?;
+        files.sort_by_key(|(_, _, modified)| *modified);
+
+        let mut state = CacheState::default();
+        for (rel_path, size, _) in files {
+            state.clock += 1;
+            state.size += size;
+            state.entries.insert(
+                rel_path,
+                CacheEntry {
+                    size,
+                    last_used: state.clock,
+                },
+            );
+        }
+        info!(
+            "opened asset cache; path={}, entries={}, size={}, max_size={}",
+            root.display(),
+            state.entries.len(),
+            state.size,
+            max_size
+        );
+
+        let cache = Cache {
+            root,
+            max_size,
+            state: Mutex::new(state),
+        };
+        cache.evict(&mut cache.state.lock().expect("lock poisoned"), None);
+
+        Ok(cache)
+    }
+
+    /// Returns the path of a cached entry and marks it as used, if the entry is present.
+    pub fn get(&self, key: &CacheKey) -> Option<PathBuf> {
+        let rel_path = key.rel_path();
+        let path = self.root.join(&rel_path);
+
+        let mut state = self.state.lock().expect("lock poisoned");
+        if !state.entries.contains_key(&rel_path) {
+            return None;
+        }
+        if !path.is_file() {
+            warn!("cache entry missing on disk; key={}", key);
+            if let Some(entry) = state.entries.remove(&rel_path) {
+                state.size -= entry.size;
+            }
+            return None;
+        }
+
+        state.clock += 1;
+        let clock = state.clock;
+        if let Some(entry) = state.entries.get_mut(&rel_path) {
+            entry.last_used = clock;
+        }
+        if let Err(err) = touch(&path) {
+            debug!(
+                "failed to set cache entry modification time; key={}, err={}",
+                key, err
+            );
+        }
+
+        Some(path)
+    }
+
+    /// Creates a temporary file for an entry which is being written, to be passed to `commit`
+    /// once complete.
+    pub fn temp_file(&self) -> io::Result<(PathBuf, File)> {
+        let name = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(16)
+            .collect::<String>();
+        let path = self.root.join(TMP_DIR).join(name);
+        let file = File::create(&path)?;
+
+        Ok((path, file))
+    }
+
+    /// Moves a completed temporary file into place as the entry for `key`, evicting other entries
+    /// as needed, and returns the path of the entry.
+    ///
+    /// An entry which is larger than the whole cache is not kept; its temporary file is returned
+    /// as is and should be removed by the caller once opened.
+    pub fn commit(&self, key: &CacheKey, temp_path: &Path) -> io::Result<(PathBuf, bool)> {
+        let size = fs::metadata(temp_path)?.len();
+        if size > self.max_size {
+            debug!(
+                "asset larger than cache, not caching; key={}, size={}",
+                key, size
+            );
+            return Ok((temp_path.to_path_buf(), false));
+        }
+
+        let rel_path = key.rel_path();
+        let path = self.root.join(&rel_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(temp_path, &path)?;
+
+        let mut state = self.state.lock().expect("lock poisoned");
+        state.clock += 1;
+        let entry = CacheEntry {
+            size,
+            last_used: state.clock,
+        };
+        if let Some(replaced) = state.entries.insert(rel_path.clone(), entry) {
+            state.size -= replaced.size;
+        }
+        state.size += size;
+        debug!("cached asset; key={}, size={}", key, size);
+        self.evict(&mut state, Some(&rel_path));
+
+        Ok((path, true))
+    }
+
+    /// Removes the least recently used entries until the cache fits its maximum size, never
+    /// removing the entry at `keep`.
+    fn evict(&self, state: &mut CacheState, keep: Option<&PathBuf>) {
+        while state.size > self.max_size {
+            let oldest = state
+                .entries
+                .iter()
+                .filter(|(rel_path, _)| Some(*rel_path) != keep)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(rel_path, _)| rel_path.clone());
+            let rel_path = match oldest {
+                Some(rel_path) => rel_path,
+                None => break,
+            };
+
+            if let Some(entry) = state.entries.remove(&rel_path) {
+                state.size -= entry.size;
+            }
+            let path = self.root.join(&rel_path);
+            match fs::remove_file(&path) {
+                Ok(_) => debug!("evicted cache entry; path={}", path.display()),
+                Err(err) => warn!(
+                    "failed to remove cache entry; path={}, err={}",
+                    path.display(),
+                    err
+                ),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Cache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Cache")
+            .field("root", &self.root)
+            .field("max_size", &self.max_size)
+            .finish()
+    }
+}
+
+/// Sets the modification time of a file to now, recording its last use across restarts.
+fn touch(path: &Path) -> io::Result<()> {
+    OpenOptions::new()
+        .write(true)
+        .open(path)?
+        .set_modified(SystemTime::now())
+}
+
+/// Collects the relative path, size and modification time of every entry below `dir`.
+fn scan_entries(
+    root: &Path,
+    dir: &Path,
+    files: &mut Vec<(PathBuf, u64, SystemTime)>,
+) -> io::Result<()> {
+    for dir_entry in fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        let path = dir_entry.path();
+        let metadata = dir_entry.metadata()?;
+
+        if metadata.is_dir() {
+            if dir == root && dir_entry.file_name() == TMP_DIR {
+                continue;
+            }
+            scan_entries(root, &path, files)?;
+        } else if metadata.is_file() {
+            let rel_path = path
+                .strip_prefix(root)
+                .expect("entry is below the cache root")
+                .to_path_buf();
+            files.push((rel_path, metadata.len(), metadata.modified()?));
+        }
+    }
+
+    Ok(())
+}
+
+/// Escapes a key component into a single file name which cannot traverse out of its directory.
+fn escape_component(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for (idx, byte) in s.bytes().enumerate() {
+        match byte {
+            b'.' if idx == 0 => escaped.push_str("%2E"),
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' => {
+                escaped.push(char::from(byte))
+            }
+            _ => escaped.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::time::Duration;
+
+    /// A cache directory below the system temp dir which is removed when dropped.
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let suffix = rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(8)
+                .collect::<String>();
+            TestDir(std::env::temp_dir().join(format!("artifetch-{}-{}", name, suffix)))
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn key(asset: &str) -> CacheKey {
+        CacheKey::new("github", "acme", "tool", 1, "rev", asset)
+    }
+
+    fn put(cache: &Cache, key: &CacheKey, size: usize) -> (PathBuf, bool) {
+        let (temp_path, mut file) = cache.temp_file().unwrap();
+        file.write_all(&vec![0; size]).unwrap();
+        drop(file);
+
+        cache.commit(key, &temp_path).unwrap()
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let dir = TestDir::new("lru");
+        let cache = Cache::open(&dir.0, 30).unwrap();
+        let (a, b, c, d) = (key("a"), key("b"), key("c"), key("d"));
+
+        put(&cache, &a, 10);
+        put(&cache, &b, 10);
+        put(&cache, &c, 10);
+        assert!(cache.get(&a).is_some());
+
+        put(&cache, &d, 10);
+        assert!(cache.get(&b).is_none());
+        assert!(!cache.root.join(b.rel_path()).exists());
+        assert!(cache.get(&a).is_some());
+        assert!(cache.get(&c).is_some());
+        assert!(cache.get(&d).is_some());
+
+        put(&cache, &b, 20);
+        assert!(cache.get(&a).is_none());
+        assert!(cache.get(&c).is_none());
+        assert!(cache.get(&d).is_some());
+        assert!(cache.get(&b).is_some());
+    }
+
+    #[test]
+    fn skips_entries_larger_than_cache() {
+        let dir = TestDir::new("oversize");
+        let cache = Cache::open(&dir.0, 10).unwrap();
+        let (a, b) = (key("a"), key("b"));
+
+        put(&cache, &a, 5);
+        let (path, cached) = put(&cache, &b, 11);
+        assert!(!cached);
+        assert!(path.starts_with(cache.root.join(TMP_DIR)));
+        assert!(cache.get(&b).is_none());
+        assert!(cache.get(&a).is_some());
+    }
+
+    #[test]
+    fn reopen_indexes_entries() {
+        let dir = TestDir::new("reopen");
+        let a = key("a");
+        {
+            let cache = Cache::open(&dir.0, 30).unwrap();
+            put(&cache, &a, 10);
+            cache.temp_file().unwrap();
+        }
+
+        let cache = Cache::open(&dir.0, 30).unwrap();
+        assert!(cache.get(&a).is_some());
+        assert_eq!(fs::read_dir(cache.root.join(TMP_DIR)).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn reopen_keeps_use_order() {
+        let dir = TestDir::new("order");
+        let (a, b, c, d) = (key("a"), key("b"), key("c"), key("d"));
+        {
+            let cache = Cache::open(&dir.0, 30).unwrap();
+            let hour_ago = SystemTime::now() - Duration::from_secs(60 * 60);
+            for (key, modified) in [(&a, hour_ago), (&b, hour_ago + Duration::from_secs(60))] {
+                let (path, _) = put(&cache, key, 10);
+                OpenOptions::new()
+                    .write(true)
+                    .open(path)
+                    .unwrap()
+                    .set_modified(modified)
+                    .unwrap();
+            }
+            assert!(cache.get(&a).is_some());
+        }
+
+        let cache = Cache::open(&dir.0, 30).unwrap();
+        put(&cache, &c, 10);
+        put(&cache, &d, 10);
+        assert!(cache.get(&b).is_none());
+        assert!(cache.get(&a).is_some());
+        assert!(cache.get(&c).is_some());
+        assert!(cache.get(&d).is_some());
+    }
+
+    #[test]
+    fn leaves_other_files_alone() {
+        let dir = TestDir::new("shared");
+        fs::create_dir_all(dir.0.join("other")).unwrap();
+        fs::write(dir.0.join("other").join("big"), vec![0; 100]).unwrap();
+        fs::write(dir.0.join("small"), vec![0; 5]).unwrap();
+
+        let cache = Cache::open(&dir.0, 10).unwrap();
+        put(&cache, &key("a"), 10);
+
+        assert!(dir.0.join("other").join("big").is_file());
+        assert!(dir.0.join("small").is_file());
+        assert_eq!(cache.root, dir.0.join(CACHE_DIR));
+    }
+
+    #[test]
+    fn revision_changes_path() {
+        let old = CacheKey::new("github", "acme", "tool", 1, "2019-01-01T00:00:00Z", "a");
+        let new = CacheKey::new("github", "acme", "tool", 1, "2019-02-01T00:00:00Z", "a");
+
+        assert_ne!(old.rel_path(), new.rel_path());
+    }
+
+    #[test]
+    fn escape_component_traversal() {
+        assert_eq!(escape_component(".."), "%2E.");
+        assert_eq!(escape_component("a/b"), "a%2Fb");
+        assert_eq!(escape_component("tool-1.0_x"), "tool-1.0_x");
+    }
+}
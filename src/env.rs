@@ -43,11 +43,9 @@ pub fn replace_vars(s: &mut String) -> Result<(), Error> {
                         return Err(Error::InvalidBraceChar(idx, ch));
                     }
                 }
-                InNoBrace(start_idx) => {
-                    if !valid_var_char(ch) {
-                        state = NoBrace(start_idx, idx);
-                        break;
-                    }
+                InNoBrace(start_idx) if !valid_var_char(ch) => {
+                    state = NoBrace(start_idx, idx);
+                    break;
                 }
                 _ => {}
             }
@@ -71,14 +69,14 @@ pub fn replace_vars(s: &mut String) -> Result<(), Error> {
             Brace(start_idx, end_idx) => {
                 let name = s.get((start_idx + 2)..end_idx).expect("range should exist");
                 let val =
-                    env::var(&name).map_err(|err| Error::EnvVarNotFound(name.to_string(), err))?;
+                    env::var(name).map_err(|err| Error::EnvVarNotFound(name.to_string(), err))?;
                 s.replace_range(start_idx..=end_idx, &val);
             }
             // Replace the not braced variable with its environment variable value
             NoBrace(start_idx, end_idx) => {
                 let name = s.get((start_idx + 1)..end_idx).expect("range should exist");
                 let val =
-                    env::var(&name).map_err(|err| Error::EnvVarNotFound(name.to_string(), err))?;
+                    env::var(name).map_err(|err| Error::EnvVarNotFound(name.to_string(), err))?;
                 s.replace_range(start_idx..end_idx, &val);
             }
             // If no variables were found, then terminate the re-scan loop
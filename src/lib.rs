@@ -1,17 +1,20 @@
 #![recursion_limit = "128"]
 
-pub use asset::Asset;
+pub use asset::{Asset, Location};
+pub use cache::{Cache, CacheKey};
 pub use etag::ETag;
-pub use provider::Provider;
+pub use provider::ReleaseSource;
 pub use registry::Registry;
 pub use release::Release;
-pub use repo::Repo;
-pub use target::Target;
+pub use repo::{ReleaseOrder, Repo};
+pub use target::{Target, TargetAliases};
 
 pub mod app;
 mod asset;
+mod cache;
 pub mod env;
 mod etag;
+pub mod metrics;
 pub mod provider;
 mod registry;
 mod release;
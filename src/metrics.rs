@@ -0,0 +1,317 @@
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Upper bounds of the HTTP request latency histogram buckets, in seconds.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+lazy_static! {
+    static ref METRICS: Metrics = Metrics::default();
+}
+
+/// Returns the metrics of the process, which updaters, providers and the server record into.
+pub fn global() -> &'static Metrics {
+    &METRICS
+}
+
+/// A repo's registry domain and `owner/name`.
+type RepoKey = (String, String);
+
+/// Counters of repo updates and HTTP requests, rendered in the Prometheus text format.
+///
+/// Gauges which are read from the registry when rendering, such as release counts, are not kept
+/// here.
+#[derive(Debug, Default)]
+pub struct Metrics(Mutex<Inner>);
+
+#[derive(Debug, Default)]
+struct Inner {
+    updates: BTreeMap<RepoKey, UpdateCounts>,
+    etags: BTreeMap<(RepoKey, &'static str), ETagCounts>,
+    requests: BTreeMap<(String, String, u16), u64>,
+    latencies: BTreeMap<String, Histogram>,
+}
+
+#[derive(Debug, Default)]
+struct UpdateCounts {
+    attempts: u64,
+    successes: u64,
+    failures: u64,
+    /// When the repo was last updated successfully, in seconds since the Unix epoch.
+    last_success: Option<f64>,
+}
+
+#[derive(Debug, Default)]
+struct ETagCounts {
+    not_modified: u64,
+    modified: u64,
+}
+
+#[derive(Debug)]
+struct Histogram {
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            buckets: vec![0; LATENCY_BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+}
+
+impl Metrics {
+    /// Records that an update of a repo was started.
+    pub fn update_started(&self, domain: &str, owner: &str, name: &str) {
+        let mut inner = self.0.lock().expect("lock poisoned");
+        inner
+            .updates
+            .entry(repo_key(domain, owner, name))
+            .or_default()
+            .attempts += 1;
+    }
+
+    /// Records that an update of a repo finished, successfully or not.
+    pub fn update_finished(&self, domain: &str, owner: &str, name: &str, success: bool) {
+        let mut inner = self.0.lock().expect("lock poisoned");
+        let counts = inner
+            .updates
+            .entry(repo_key(domain, owner, name))
+            .or_default();
+        if success {
+            counts.successes += 1;
+            counts.last_success = Some(unix_now());
+        } else {
+            counts.failures += 1;
+        }
+    }
+
+    /// Records the answer to a conditional request for a repo's `resource`, such as `releases` or
+    /// `latest`, made with the entity tag of the previous response.
+    pub fn etag_response(
+        &self,
+        domain: &str,
+        owner: &str,
+        name: &str,
+        resource: &'static str,
+        modified: bool,
+    ) {
+        let mut inner = self.0.lock().expect("lock poisoned");
+        let counts = inner
+            .etags
+            .entry((repo_key(domain, owner, name), resource))
+            .or_default();
+        if modified {
+            counts.modified += 1;
+        } else {
+            counts.not_modified += 1;
+        }
+    }
+
+    /// Forgets the metrics of a repo which was removed from the registry.
+    pub fn remove_repo(&self, domain: &str, owner: &str, name: &str) {
+        let mut inner = self.0.lock().expect("lock poisoned");
+        let key = repo_key(domain, owner, name);
+        inner.updates.remove(&key);
+        inner.etags.retain(|(repo, _), _| *repo != key);
+    }
+
+    /// Records an HTTP request served for a route.
+    pub fn http_request(&self, route: &str, method: &str, status: u16, latency: Duration) {
+        let mut inner = self.0.lock().expect("lock poisoned");
+        *inner
+            .requests
+            .entry((route.to_string(), method.to_string(), status))
+            .or_default() += 1;
+
+        let secs = latency.as_secs() as f64 + f64::from(latency.subsec_nanos()) / 1e9;
+        let histogram = inner.latencies.entry(route.to_string()).or_default();
+        for (bucket, bound) in histogram.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if secs <= *bound {
+                *bucket += 1;
+            }
+        }
+        histogram.sum += secs;
+        histogram.count += 1;
+    }
+
+    /// Renders the recorded metrics in the Prometheus text format.
+    pub fn render(&self, out: &mut String) {
+        let inner = self.0.lock().expect("lock poisoned");
+
+        let updates = [
+            (
+                "artifetch_update_attempts_total",
+                "Updates of a repo which were started.",
+            ),
+            (
+                "artifetch_update_successes_total",
+                "Updates of a repo which succeeded.",
+            ),
+            (
+                "artifetch_update_failures_total",
+                "Updates of a repo which failed.",
+            ),
+        ];
+        for (i, (name, help)) in updates.iter().enumerate() {
+            let mut family = Family::new(out, name, "counter", help);
+            for ((domain, repo), counts) in &inner.updates {
+                let value = [counts.attempts, counts.successes, counts.failures][i];
+                family.sample(&[("domain", domain), ("repo", repo)], value as f64);
+            }
+        }
+
+        let mut family = Family::new(
+            out,
+            "artifetch_last_successful_update_timestamp_seconds",
+            "gauge",
+            "When a repo was last updated successfully.",
+        );
+        for ((domain, repo), counts) in &inner.updates {
+            if let Some(last_success) = counts.last_success {
+                family.sample(&[("domain", domain), ("repo", repo)], last_success);
+            }
+        }
+
+        let mut family = Family::new(
+            out,
+            "artifetch_etag_responses_total",
+            "counter",
+            "Conditional requests for a repo's releases, by whether they were not modified.",
+        );
+        for (((domain, repo), resource), counts) in &inner.etags {
+            for (result, value) in &[
+                ("not_modified", counts.not_modified),
+                ("modified", counts.modified),
+            ] {
+                family.sample(
+                    &[
+                        ("domain", domain),
+                        ("repo", repo),
+                        ("resource", resource),
+                        ("result", result),
+                    ],
+                    *value as f64,
+                );
+            }
+        }
+
+        let mut family = Family::new(
+            out,
+            "artifetch_etag_not_modified_ratio",
+            "gauge",
+            "The fraction of conditional requests for a repo's releases which were not modified.",
+        );
+        for (((domain, repo), resource), counts) in &inner.etags {
+            let total = counts.not_modified + counts.modified;
+            if total > 0 {
+                family.sample(
+                    &[("domain", domain), ("repo", repo), ("resource", resource)],
+                    counts.not_modified as f64 / total as f64,
+                );
+            }
+        }
+
+        let mut family = Family::new(
+            out,
+            "artifetch_http_requests_total",
+            "counter",
+            "HTTP requests served, by route, method and status.",
+        );
+        for ((route, method, status), value) in &inner.requests {
+            family.sample(
+                &[
+                    ("route", route),
+                    ("method", method),
+                    ("status", &status.to_string()),
+                ],
+                *value as f64,
+            );
+        }
+
+        let mut family = Family::new(
+            out,
+            "artifetch_http_request_duration_seconds",
+            "histogram",
+            "Latencies of HTTP requests served, by route.",
+        );
+        for (route, histogram) in &inner.latencies {
+            for (bound, value) in LATENCY_BUCKETS.iter().zip(&histogram.buckets) {
+                family.sample_named(
+                    "_bucket",
+                    &[("route", route), ("le", &bound.to_string())],
+                    *value as f64,
+                );
+            }
+            family.sample_named(
+                "_bucket",
+                &[("route", route), ("le", "+Inf")],
+                histogram.count as f64,
+            );
+            family.sample_named("_sum", &[("route", route)], histogram.sum);
+            family.sample_named("_count", &[("route", route)], histogram.count as f64);
+        }
+    }
+}
+
+/// Writes the samples of a metric family in the Prometheus text format.
+pub struct Family<'a> {
+    out: &'a mut String,
+    name: &'a str,
+}
+
+impl<'a> Family<'a> {
+    /// Starts a metric family of a `kind`, such as `counter` or `gauge`, writing its help text.
+    pub fn new(out: &'a mut String, name: &'a str, kind: &str, help: &str) -> Self {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+
+        Family { out, name }
+    }
+
+    pub fn sample(&mut self, labels: &[(&str, &str)], value: f64) {
+        self.sample_named("", labels, value);
+    }
+
+    /// Writes a sample whose name has a suffix, such as the `_bucket` of a histogram.
+    pub fn sample_named(&mut self, suffix: &str, labels: &[(&str, &str)], value: f64) {
+        let labels = labels
+            .iter()
+            .map(|(name, value)| format!("{}=\"{}\"", name, escape_label(value)))
+            .collect::<Vec<_>>();
+        let _ = writeln!(
+            self.out,
+            "{}{}{{{}}} {}",
+            self.name,
+            suffix,
+            labels.join(","),
+            value
+        );
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn repo_key(domain: &str, owner: &str, name: &str) -> RepoKey {
+    (domain.to_string(), format!("{}/{}", owner, name))
+}
+
+fn unix_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs() as f64)
+        .unwrap_or(0.0)
+}
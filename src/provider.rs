@@ -1,65 +1,116 @@
-use crate::Repo;
-use actix_web::http::uri;
-use futures::Future;
-use github::GitHub;
+use crate::{Asset, Repo};
+use actix_web::http::{uri, Uri};
+use chrono::{DateTime, Utc};
+use futures::{future, Future};
+use infer::TargetInference;
 use std::error;
 use std::fmt;
+use std::io;
 use std::sync::Arc;
 
+mod conditional;
+pub mod filesystem;
+pub mod gitea;
 pub mod github;
+pub mod gitlab;
+pub mod http;
+pub mod infer;
+pub mod manifest;
+pub mod proxy;
+pub mod rate_limit;
+mod repo_map;
+pub mod s3;
 
-#[derive(Debug)]
-pub enum Provider {
-    GitHub(GitHub),
+const NO_ETAG: &str = "<none>";
+
+/// How releases are paged through when listing them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pagination {
+    /// The number of releases requested per page, up to GitHub's maximum of 100.
+    pub per_page: u32,
+    /// The maximum number of releases to list, if any.
+    pub max_releases: Option<usize>,
 }
 
-impl Provider {
-    pub fn domain(&self) -> &str {
-        match self {
-            Provider::GitHub(github) => github.domain(),
+impl Default for Pagination {
+    fn default() -> Self {
+        Pagination {
+            per_page: 100,
+            max_releases: None,
         }
     }
+}
 
-    pub fn repos<'a>(&'a self) -> impl Iterator<Item = Arc<Repo>> + 'a {
-        match self {
-            Provider::GitHub(github) => github.repos(),
-        }
+/// A source of releases for a set of repos.
+///
+/// Each configured registry entry is backed by a release source, which owns its repos and keeps
+/// them current when asked to update. Implementations outside this crate can be registered
+/// through a factory in `app::Factories`.
+pub trait ReleaseSource: fmt::Debug + fmt::Display + Send + Sync {
+    /// Returns the domain under which the source is registered.
+    fn domain(&self) -> &str;
+
+    /// Returns every repo of the source.
+    fn repos<'a>(&'a self) -> Box<dyn Iterator<Item = Arc<Repo>> + 'a>;
+
+    /// Returns a repo of the source, if it exists.
+    fn repo(&self, owner: &str, name: &str) -> Option<Arc<Repo>>;
+
+    /// Returns a future which refreshes the releases of a repo.
+    fn update_repo(&self, owner: &str, name: &str) -> Box<dyn Future<Item = (), Error = Error>>;
+
+    /// Restores the releases of a repo from a snapshot, such as one persisted by a previous run,
+    /// keeping the repo's own config.
+    ///
+    /// Sources which do not support restoring leave their repos to be populated by updates.
+    fn restore_repo(&self, snapshot: Repo) -> Result<(), Error> {
+        let _ = snapshot;
+        Ok(())
     }
 
-    pub fn repo<S, T>(&self, owner: S, name: T) -> Option<Arc<Repo>>
-    where
-        S: AsRef<str>,
-        T: AsRef<str>,
-    {
-        match self {
-            Provider::GitHub(github) => github.repo(owner, name),
-        }
+    /// Returns how the source pages through releases, for sources where this is configurable.
+    ///
+    /// Saved entity tags are dropped when the pagination changes, as the saved releases may no
+    /// longer match what the source would list.
+    fn pagination(&self) -> Option<Pagination> {
+        None
     }
 
-    pub fn update_repo<O, N>(&self, owner: O, name: N) -> impl Future<Item = (), Error = Error>
-    where
-        O: Into<String>,
-        N: Into<String>,
-    {
-        match self {
-            Provider::GitHub(github) => github.update_repo(owner, name),
-        }
+    /// Returns the budget of the source's API rate limit, for sources which have one.
+    ///
+    /// Updaters are slowed when the budget runs low and paused when it is exhausted.
+    fn rate_limit(&self) -> Option<rate_limit::Budget> {
+        None
     }
-}
 
-impl fmt::Display for Provider {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Provider::GitHub(github) => github.fmt(f),
-        }
+    /// Returns the URI which clients are redirected to when downloading a remote asset.
+    fn download_uri(&self, uri: &Uri) -> Result<Uri, Error> {
+        Ok(uri.clone())
+    }
+
+    /// Returns a future which fetches the bytes of a remote asset through the source's client, for
+    /// repos which proxy downloads rather than redirecting clients.
+    ///
+    /// The `range` is the `Range` header of the download request, if any, which is passed on.
+    fn fetch_asset(
+        &self,
+        asset: &Asset,
+        range: Option<String>,
+    ) -> Box<dyn Future<Item = proxy::AssetStream, Error = Error>> {
+        let _ = (asset, range);
+        Box::new(future::err(Error::ProxyUnsupported))
     }
 }
 
 #[derive(Debug)]
 pub enum Error {
     Client(Box<dyn error::Error + Send + Sync>),
+    InvalidAssetPath(String),
     InvalidUri(String, uri::InvalidUri),
-    LatestNotFound,
+    Io(io::Error),
+    Manifest(manifest::ParseError),
+    ProxyUnsupported,
+    RateLimited(DateTime<Utc>),
     RepoNotFound,
 }
 
@@ -67,10 +118,16 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Error::Client(ref err) => err.fmt(f),
+            Error::InvalidAssetPath(ref path) => write!(f, "invalid asset path: {}", path),
             Error::InvalidUri(ref uri_str, ref err) => {
                 write!(f, "invalid uri {}: {}", uri_str, err)
             }
-            Error::LatestNotFound => f.write_str("latest release not found"),
+            Error::Io(ref err) => err.fmt(f),
+            Error::Manifest(ref err) => err.fmt(f),
+            Error::ProxyUnsupported => f.write_str("provider does not support proxying assets"),
+            Error::RateLimited(ref until) => {
+                write!(f, "rate limit exceeded until {}", until.to_rfc3339())
+            }
             Error::RepoNotFound => f.write_str("repository not found"),
         }
     }
@@ -80,15 +137,107 @@ impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
             Error::Client(ref err) => err.source(),
+            Error::InvalidAssetPath(_) => None,
             Error::InvalidUri(_, ref err) => err.source(),
-            Error::LatestNotFound => None,
+            Error::Io(ref err) => err.source(),
+            Error::Manifest(ref err) => err.source(),
+            Error::ProxyUnsupported => None,
+            Error::RateLimited(_) => None,
             Error::RepoNotFound => None,
         }
     }
 }
 
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<manifest::ParseError> for Error {
+    fn from(err: manifest::ParseError) -> Self {
+        Error::Manifest(err)
+    }
+}
+
+impl<E: http::ErrorBody> From<http::Error<E>> for Error {
+    fn from(err: http::Error<E>) -> Self {
+        match err {
+            http::Error::RateLimited(until) => Error::RateLimited(rate_limit::timestamp(until)),
+            err => Error::Client(Box::new(err)),
+        }
+    }
+}
+
 impl From<github::client::Error> for Error {
     fn from(err: github::client::Error) -> Self {
+        match err {
+            github::client::Error::Http(err) => Error::from(err),
+            github::client::Error::InstallationToken(ref token_err) => match **token_err {
+                github::client::Error::Http(http::Error::RateLimited(until)) => {
+                    Error::RateLimited(rate_limit::timestamp(until))
+                }
+                _ => Error::Client(Box::new(err)),
+            },
+            err => Error::Client(Box::new(err)),
+        }
+    }
+}
+
+impl From<s3::client::Error> for Error {
+    fn from(err: s3::client::Error) -> Self {
         Error::Client(Box::new(err))
     }
 }
+
+/// The kinds of releases which are included for a repo.
+#[derive(Clone, Copy, Debug)]
+struct ReleaseFilter {
+    prereleases: bool,
+    drafts: bool,
+}
+
+impl ReleaseFilter {
+    fn for_repo(repo: &Repo) -> Self {
+        ReleaseFilter {
+            prereleases: repo.prereleases(),
+            drafts: repo.drafts(),
+        }
+    }
+
+    fn includes(self, draft: bool, prerelease: bool) -> bool {
+        (!draft || self.drafts) && (!prerelease || self.prereleases)
+    }
+}
+
+/// How the releases of a repo are filtered and have their targets found.
+#[derive(Clone, Debug)]
+struct ReleaseOptions {
+    filter: ReleaseFilter,
+    inference: TargetInference,
+}
+
+impl ReleaseOptions {
+    fn for_repo(repo: &Repo) -> Self {
+        ReleaseOptions {
+            filter: ReleaseFilter::for_repo(repo),
+            inference: repo.target_inference().clone(),
+        }
+    }
+}
+
+/// Returns a stable release id derived from a tag name, for providers which do not assign
+/// numeric ids to their releases.
+fn release_id(tag_name: &str) -> u64 {
+    // 64-bit FNV-1a, chosen as it is stable across builds and platforms
+    tag_name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Parses an RFC 3339 timestamp, as returned by provider APIs.
+fn parse_timestamp(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+}
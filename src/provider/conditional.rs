@@ -0,0 +1,223 @@
+use super::http::Response;
+use super::repo_map::RepoMap;
+use super::{Error, NO_ETAG};
+use crate::{metrics, ETag, Repo};
+use futures::{
+    future::{self, Either},
+    Future, IntoFuture,
+};
+use log::info;
+use std::sync::Arc;
+
+/// A resource of a repo which is fetched with a conditional request against its last ETag.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Resource {
+    Releases,
+    Latest,
+}
+
+impl Resource {
+    fn label(self) -> &'static str {
+        match self {
+            Resource::Releases => "releases",
+            Resource::Latest => "latest",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Resource::Releases => "releases",
+            Resource::Latest => "latest release",
+        }
+    }
+
+    fn set_etag(self, repo: &mut Repo, etag: Option<ETag>) {
+        match self {
+            Resource::Releases => repo.set_releases_etag(etag),
+            Resource::Latest => repo.set_latest_etag(etag),
+        }
+    }
+}
+
+/// Treats a missing resource as a modified one without a payload, so that its absence is applied
+/// to the repo rather than failing the update. A repo which only has prereleases has no latest
+/// release, for example.
+pub(crate) fn missing_as_none<T, E, F>(
+    result: Result<Option<Response<T>>, E>,
+    is_not_found: F,
+) -> Result<Option<Response<Option<T>>>, E>
+where
+    F: FnOnce(&E) -> bool,
+{
+    match result {
+        Ok(response) => Ok(response.map(|response| response.map(Some))),
+        Err(ref err) if is_not_found(err) => Ok(Some(Response::new(None, None))),
+        Err(err) => Err(err),
+    }
+}
+
+/// Applies the response of a conditional request to a repo, recording the ETag metrics and
+/// leaving the repo untouched when the resource was not modified.
+pub(crate) struct ConditionalUpdate {
+    repos: RepoMap,
+    domain: Arc<str>,
+    owner: Arc<str>,
+    name: Arc<str>,
+    resource: Resource,
+    etag: Option<ETag>,
+}
+
+impl ConditionalUpdate {
+    pub(crate) fn new(
+        repos: RepoMap,
+        domain: Arc<str>,
+        owner: Arc<str>,
+        name: Arc<str>,
+        resource: Resource,
+        etag: Option<ETag>,
+    ) -> Self {
+        ConditionalUpdate {
+            repos,
+            domain,
+            owner,
+            name,
+            resource,
+            etag,
+        }
+    }
+
+    pub(crate) fn etag(&self) -> Option<&ETag> {
+        self.etag.as_ref()
+    }
+
+    /// Converts the payload of a modified resource with `process` and applies the resulting
+    /// update to the repo along with the resource's next ETag.
+    pub(crate) fn apply<T, P, F, U>(
+        self,
+        response: Option<Response<T>>,
+        process: P,
+    ) -> impl Future<Item = (), Error = Error>
+    where
+        P: FnOnce(T) -> F,
+        F: IntoFuture<Item = U, Error = Error>,
+        U: FnOnce(&mut Repo),
+    {
+        let ConditionalUpdate {
+            repos,
+            domain,
+            owner,
+            name,
+            resource,
+            etag,
+        } = self;
+
+        match response {
+            None => {
+                metrics::global().etag_response(&domain, &owner, &name, resource.label(), false);
+                info!(
+                    "{} not modified; domain={}, repo={}/{}, etag={}",
+                    resource.description(),
+                    &domain,
+                    &owner,
+                    &name,
+                    etag.as_ref().map(|e| e.as_ref()).unwrap_or_else(|| NO_ETAG)
+                );
+
+                Either::A(future::ok(()))
+            }
+            Some(response) => {
+                let (next_etag, payload) = response.into_parts();
+                if etag.is_some() {
+                    metrics::global().etag_response(&domain, &owner, &name, resource.label(), true);
+                }
+
+                Either::B(process(payload).into_future().and_then(move |update| {
+                    repos
+                        .repo_mut(&owner, &name, |repo| {
+                            resource.set_etag(repo, next_etag.clone());
+                            update(repo);
+                        })
+                        .map(|_| {
+                            info!(
+                                "{} updated; domain={}, repo={}/{}, next_etag={}",
+                                resource.description(),
+                                &domain,
+                                &owner,
+                                &name,
+                                next_etag
+                                    .as_ref()
+                                    .map(|e| e.as_ref())
+                                    .unwrap_or_else(|| NO_ETAG)
+                            );
+                        })
+                }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Release;
+
+    fn repos() -> RepoMap {
+        let mut repo = Repo::new("acme", "tool");
+        repo.set_releases(vec![Release::new(1, "v1.0.0")]);
+        repo.set_latest_release(Some("v1.0.0"));
+        repo.set_latest_etag(Some("\"latest\"".parse().unwrap()));
+
+        RepoMap::new(vec![repo])
+    }
+
+    fn apply_latest(
+        repos: &RepoMap,
+        result: Result<Option<Response<&'static str>>, &'static str>,
+    ) -> Result<(), Error> {
+        let update = ConditionalUpdate::new(
+            repos.clone(),
+            "github.com".into(),
+            "acme".into(),
+            "tool".into(),
+            Resource::Latest,
+            repos.repo("acme", "tool").unwrap().latest_etag().cloned(),
+        );
+        let response = missing_as_none(result, |err| *err == "not found")
+            .map_err(|err| Error::Client(err.into()))?;
+
+        update
+            .apply(response, |latest| {
+                future::ok(move |repo: &mut Repo| repo.set_latest_release(latest))
+            })
+            .wait()
+    }
+
+    #[test]
+    fn missing_latest_release_is_cleared() {
+        let repos = repos();
+        apply_latest(&repos, Err("not found")).unwrap();
+
+        let repo = repos.repo("acme", "tool").unwrap();
+        assert!(repo.latest_release().is_none());
+        assert!(repo.latest_etag().is_none());
+    }
+
+    #[test]
+    fn unmodified_latest_release_is_kept() {
+        let repos = repos();
+        apply_latest(&repos, Ok(None)).unwrap();
+
+        let repo = repos.repo("acme", "tool").unwrap();
+        assert_eq!(repo.latest_release().map(Release::name), Some("v1.0.0"));
+        assert_eq!(repo.latest_etag().map(ETag::as_str), Some("\"latest\""));
+    }
+
+    #[test]
+    fn other_errors_fail_the_update() {
+        let repos = repos();
+        assert!(apply_latest(&repos, Err("server error")).is_err());
+
+        let repo = repos.repo("acme", "tool").unwrap();
+        assert_eq!(repo.latest_release().map(Release::name), Some("v1.0.0"));
+    }
+}
@@ -0,0 +1,221 @@
+use super::infer::TargetInference;
+use super::manifest::{self, Manifest};
+use super::repo_map::RepoMap;
+use super::{release_id, Error, ReleaseSource};
+use crate::{Asset, Release, Repo};
+use actix_web::{error::BlockingError, web};
+use futures::{future, Future};
+use log::info;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+
+/// A provider which indexes releases from a local directory tree.
+///
+/// The tree is laid out as `<root>/<owner>/<repo>/<version>/`, where each version directory
+/// contains the release's `*.manifest.txt` files alongside the assets they reference.
+pub struct Filesystem {
+    domain: String,
+    root: PathBuf,
+    repos: RepoMap,
+}
+
+impl fmt::Display for Filesystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "provider::filesystem({})", &self.domain)
+    }
+}
+
+impl fmt::Debug for Filesystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Filesystem")
+            .field("domain", &self.domain)
+            .field("root", &self.root)
+            .field("repos", &self.repos)
+            .finish()
+    }
+}
+
+impl Filesystem {
+    pub fn build<S, P, R>(domain: S, root: P, iter: R) -> Result<Self, Error>
+    where
+        S: Into<String>,
+        P: Into<PathBuf>,
+        R: IntoIterator<Item = Repo>,
+    {
+        let domain = domain.into();
+        let root = root.into();
+        if !root.is_dir() {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("root directory not found: {}", root.display()),
+            )));
+        }
+        let repos = RepoMap::new(iter);
+
+        Ok(Filesystem {
+            domain,
+            root,
+            repos,
+        })
+    }
+}
+
+impl ReleaseSource for Filesystem {
+    fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    fn repos<'a>(&'a self) -> Box<dyn Iterator<Item = Arc<Repo>> + 'a> {
+        Box::new(self.repos.repos())
+    }
+
+    fn repo(&self, owner: &str, name: &str) -> Option<Arc<Repo>> {
+        self.repos.repo(owner, name)
+    }
+
+    fn restore_repo(&self, snapshot: Repo) -> Result<(), Error> {
+        self.repos.restore(snapshot)
+    }
+
+    fn update_repo(&self, owner: &str, name: &str) -> Box<dyn Future<Item = (), Error = Error>> {
+        let owner = owner.to_string();
+        let name = name.to_string();
+
+        let inference = match self.repo(&owner, &name) {
+            Some(repo) => repo.target_inference().clone(),
+            None => return Box::new(future::err(Error::RepoNotFound)),
+        };
+
+        let repos = self.repos.clone();
+        let domain = self.domain.clone();
+        let repo_dir = self.root.join(&owner).join(&name);
+        let repo_name = name.clone();
+
+        Box::new(
+            web::block(move || scan_releases(&repo_dir, &repo_name, &inference))
+                .map_err(|err| match err {
+                    BlockingError::Error(err) => err,
+                    BlockingError::Canceled => {
+                        Error::Io(io::Error::other("directory scan was canceled"))
+                    }
+                })
+                .and_then(move |(releases, latest)| {
+                    repos.repo_mut(&owner, &name, |repo| {
+                        repo.set_releases(releases);
+                        repo.set_latest_prerelease(latest.clone());
+                        repo.set_latest_release(latest);
+                        repo.set_updated(Some(Instant::now()));
+                    })?;
+                    info!(
+                        "releases updated; domain={}, repo={}/{}",
+                        &domain, &owner, &name
+                    );
+
+                    Ok(())
+                }),
+        )
+    }
+}
+
+/// Scans a repo directory, returning its releases and the name of the latest release.
+///
+/// The latest release is the version directory which was most recently modified.
+fn scan_releases(
+    repo_dir: &Path,
+    repo_name: &str,
+    inference: &TargetInference,
+) -> Result<(Vec<Release>, Option<String>), Error> {
+    let mut releases = Vec::new();
+    let mut latest = None::<(SystemTime, String)>;
+
+    for entry in fs::read_dir(repo_dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let version = match entry.file_name().into_string() {
+            Ok(version) => version,
+            Err(_) => continue,
+        };
+
+        let modified = entry.metadata()?.modified()?;
+        if latest.as_ref().map(|l| modified > l.0).unwrap_or(true) {
+            latest = Some((modified, version.clone()));
+        }
+        let mut release = scan_release(&entry.path(), version, repo_name, inference)?;
+        release.set_created(Some(modified.into()));
+        releases.push(release);
+    }
+
+    Ok((releases, latest.map(|l| l.1)))
+}
+
+fn scan_release(
+    version_dir: &Path,
+    version: String,
+    repo_name: &str,
+    inference: &TargetInference,
+) -> Result<Release, Error> {
+    let mut manifests = Vec::new();
+    let mut file_names = Vec::new();
+    for entry in fs::read_dir(version_dir)? {
+        let entry = entry?;
+        if !entry.path().is_file() {
+            continue;
+        }
+        let file_name = match entry.file_name().into_string() {
+            Ok(file_name) => file_name,
+            Err(_) => continue,
+        };
+
+        if manifest::is_manifest(&file_name) {
+            manifests.push(Manifest::from_bytes(
+                file_name.as_str(),
+                fs::read(entry.path())?,
+            )?);
+        }
+        file_names.push(file_name);
+    }
+    if !manifest::has_targets(&manifests) {
+        manifests.extend(inference.manifests(file_names.iter().map(String::as_str), repo_name));
+    }
+
+    let targets = manifest::targets_from_manifests(manifests, |name, asset| {
+        let path = path_for_asset(version_dir, asset)?;
+        let size = fs::metadata(&path)?.len();
+
+        let mut asset = Asset::new(name, path);
+        asset.set_size(Some(size));
+
+        Ok(asset)
+    })?;
+
+    let mut release = Release::new(release_id(&version), version);
+    release.set_targets(targets);
+
+    Ok(release)
+}
+
+/// Returns the path of an asset in a version directory, ensuring that the asset name from the
+/// manifest can't escape that directory.
+fn path_for_asset(version_dir: &Path, asset: &str) -> Result<PathBuf, Error> {
+    let mut components = Path::new(asset).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => {}
+        _ => return Err(Error::InvalidAssetPath(asset.to_string())),
+    }
+
+    let path = version_dir.join(asset);
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(Error::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("asset not found: {}", path.display()),
+        )))
+    }
+}
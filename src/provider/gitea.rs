@@ -0,0 +1,317 @@
+use super::conditional::{missing_as_none, ConditionalUpdate, Resource};
+use super::http;
+use super::infer::TargetInference;
+use super::manifest;
+use super::proxy::AssetStream;
+use super::repo_map::RepoMap;
+use super::{parse_timestamp, Error, ReleaseOptions, ReleaseSource};
+use crate::{Asset, ETag, Release, Repo};
+use actix_web::http::{HttpTryFrom, Uri};
+use futures::{future, Future};
+use std::fmt;
+use std::sync::Arc;
+
+pub mod client;
+
+pub struct Gitea {
+    domain: String,
+    client: Arc<client::Client>,
+    repos: RepoMap,
+}
+
+impl fmt::Display for Gitea {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "provider::gitea({})", &self.domain)
+    }
+}
+
+impl fmt::Debug for Gitea {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Gitea")
+            .field("domain", &self.domain)
+            .field("repos", &self.repos)
+            .finish()
+    }
+}
+
+impl Gitea {
+    pub fn build<S, O, R>(domain: S, oauth_token: O, iter: R) -> Result<Self, Error>
+    where
+        S: Into<String>,
+        O: AsRef<str>,
+        R: IntoIterator<Item = Repo>,
+    {
+        let domain = domain.into();
+        let client = Arc::new(client::Client::build(&domain, oauth_token)?);
+        let repos = RepoMap::new(iter);
+
+        Ok(Gitea {
+            domain,
+            client,
+            repos,
+        })
+    }
+}
+
+impl ReleaseSource for Gitea {
+    fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    fn repos<'a>(&'a self) -> Box<dyn Iterator<Item = Arc<Repo>> + 'a> {
+        Box::new(self.repos.repos())
+    }
+
+    fn repo(&self, owner: &str, name: &str) -> Option<Arc<Repo>> {
+        self.repos.repo(owner, name)
+    }
+
+    fn restore_repo(&self, snapshot: Repo) -> Result<(), Error> {
+        self.repos.restore(snapshot)
+    }
+
+    fn update_repo(&self, owner: &str, name: &str) -> Box<dyn Future<Item = (), Error = Error>> {
+        let owner: Arc<str> = owner.into();
+        let name: Arc<str> = name.into();
+        let domain: Arc<str> = self.domain.clone().into();
+
+        let (releases_etag, latest_etag, options) = {
+            let repo = match self.repo(&owner, &name) {
+                Some(repo) => repo,
+                None => return Box::new(future::err(Error::RepoNotFound)),
+            };
+
+            (
+                repo.releases_etag().cloned(),
+                repo.latest_etag().cloned(),
+                ReleaseOptions::for_repo(&repo),
+            )
+        };
+
+        let repos = self.repos.clone();
+
+        Box::new(
+            update_releases(
+                self.client.clone(),
+                self.repos.clone(),
+                domain.clone(),
+                owner.clone(),
+                name.clone(),
+                releases_etag,
+                options,
+            )
+            .join(update_latest(
+                self.client.clone(),
+                self.repos.clone(),
+                domain,
+                owner.clone(),
+                name.clone(),
+                latest_etag,
+            ))
+            .and_then(move |_| repos.mark_updated(&owner, &name)),
+        )
+    }
+
+    fn fetch_asset(
+        &self,
+        asset: &Asset,
+        range: Option<String>,
+    ) -> Box<dyn Future<Item = AssetStream, Error = Error>> {
+        let uri = match asset.proxy_uri() {
+            Some(uri) => uri.to_string(),
+            None => return Box::new(future::err(Error::ProxyUnsupported)),
+        };
+
+        Box::new(
+            self.client
+                .download(uri, range.as_deref())
+                .map(AssetStream::from_response)
+                .map_err(Error::from),
+        )
+    }
+}
+
+fn update_releases(
+    client: Arc<client::Client>,
+    repos: RepoMap,
+    domain: Arc<str>,
+    owner: Arc<str>,
+    name: Arc<str>,
+    etag: Option<ETag>,
+    options: ReleaseOptions,
+) -> impl Future<Item = (), Error = Error> {
+    let indexed = repos.repo(&owner, &name);
+    let update = ConditionalUpdate::new(
+        repos,
+        domain,
+        owner.clone(),
+        name.clone(),
+        Resource::Releases,
+        etag,
+    );
+
+    client
+        .releases(owner.clone(), name.clone(), update.etag())
+        .map_err(Error::from)
+        .and_then(move |response| {
+            update.apply(response, move |releases| {
+                process_releases(client, releases, indexed, name, options).map(
+                    |(releases, latest_prerelease)| {
+                        move |repo: &mut Repo| {
+                            repo.set_releases(releases);
+                            repo.set_latest_prerelease(latest_prerelease);
+                        }
+                    },
+                )
+            })
+        })
+}
+
+fn update_latest(
+    client: Arc<client::Client>,
+    repos: RepoMap,
+    domain: Arc<str>,
+    owner: Arc<str>,
+    name: Arc<str>,
+    etag: Option<ETag>,
+) -> impl Future<Item = (), Error = Error> {
+    let update = ConditionalUpdate::new(
+        repos,
+        domain,
+        owner.clone(),
+        name.clone(),
+        Resource::Latest,
+        etag,
+    );
+
+    client
+        .latest_release(owner, name, update.etag())
+        .then(|result| missing_as_none(result, |err| matches!(err, http::Error::NotFound)))
+        .map_err(Error::from)
+        .and_then(move |response| {
+            update.apply(response, |latest| {
+                future::ok(move |repo: &mut Repo| {
+                    repo.set_latest_release(latest.map(|latest| latest.tag_name))
+                })
+            })
+        })
+}
+
+fn process_releases(
+    client: Arc<client::Client>,
+    releases: Vec<client::Release>,
+    indexed: Option<Arc<Repo>>,
+    name: Arc<str>,
+    options: ReleaseOptions,
+) -> impl Future<Item = (Vec<Release>, Option<String>), Error = Error> {
+    let filtered_releases = releases
+        .into_iter()
+        .filter(|rel| options.filter.includes(rel.draft, rel.prerelease))
+        .collect::<Vec<_>>();
+    let latest_prerelease = filtered_releases
+        .iter()
+        .max_by(|a, b| a.created_at.cmp(&b.created_at))
+        .map(|rel| rel.tag_name.clone());
+
+    let mut all_manifests = Vec::new();
+    for release in &filtered_releases {
+        let mut files = manifest::manifest_files(release.assets.iter().map(|a| a.name.as_str()));
+        let reused = manifest::reuse_checksums(
+            &mut files,
+            indexed
+                .as_ref()
+                .and_then(|repo| repo.release(&release.tag_name)),
+            |file_name, asset| {
+                release.assets.iter().any(|a| {
+                    a.name == file_name
+                        && asset.revision() == Some(a.uuid.as_str())
+                        && asset
+                            .download_uri()
+                            .is_some_and(|uri| uri == a.browser_download_url.as_str())
+                })
+            },
+        );
+        all_manifests.push(
+            future::join_all(
+                release
+                    .assets
+                    .iter()
+                    .filter(|asset| files.contains(asset.name.as_str()))
+                    .map(|asset| {
+                        client
+                            .manifest(asset.browser_download_url.clone(), asset.name.clone())
+                            .map_err(Error::from)
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .map(move |mut manifests| {
+                manifests.push(reused);
+                manifests
+            }),
+        );
+    }
+
+    future::join_all(all_manifests).and_then(move |all_manifests| {
+        convert_releases(filtered_releases, all_manifests, &options.inference, &name)
+            .map(|releases| (releases, latest_prerelease))
+    })
+}
+
+fn convert_releases(
+    client_releases: Vec<client::Release>,
+    client_all_manifests: Vec<Vec<manifest::Manifest>>,
+    inference: &TargetInference,
+    repo_name: &str,
+) -> Result<Vec<Release>, Error> {
+    let mut releases = Vec::new();
+    for (release, manifests) in client_releases.into_iter().zip(client_all_manifests) {
+        releases.push(convert_release(release, manifests, inference, repo_name)?);
+    }
+
+    Ok(releases)
+}
+
+fn convert_release(
+    release: client::Release,
+    mut manifests: Vec<manifest::Manifest>,
+    inference: &TargetInference,
+    repo_name: &str,
+) -> Result<Release, Error> {
+    if !manifest::has_targets(&manifests) {
+        manifests
+            .extend(inference.manifests(release.assets.iter().map(|a| a.name.as_str()), repo_name));
+    }
+    let targets = manifest::targets_from_manifests(manifests, |name, asset| {
+        asset_for_entry(name, asset, &release.assets)
+    })?;
+
+    let mut converted = Release::from(release);
+    converted.set_targets(targets);
+
+    Ok(converted)
+}
+
+fn asset_for_entry(name: String, gt_name: &str, assets: &[client::Asset]) -> Result<Asset, Error> {
+    let client_asset = assets
+        .iter()
+        .find(|a| a.name == gt_name)
+        .ok_or_else(|| client::Error::MissingResponseField("browser_download_url"))?;
+    let uri_str = &client_asset.browser_download_url;
+    let uri = Uri::try_from(uri_str).map_err(|err| Error::InvalidUri(uri_str.to_string(), err))?;
+
+    let mut asset = Asset::new(name, uri);
+    asset.set_size(Some(client_asset.size));
+    asset.set_revision(Some(client_asset.uuid.as_str()));
+
+    Ok(asset)
+}
+
+impl From<client::Release> for Release {
+    fn from(cr: client::Release) -> Self {
+        let mut release = Release::new(cr.id, cr.tag_name);
+        release.set_prerelease(cr.prerelease);
+        release.set_created(parse_timestamp(&cr.created_at));
+
+        release
+    }
+}
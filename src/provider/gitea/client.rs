@@ -0,0 +1,150 @@
+use crate::provider::http::{self, ErrorBody, HttpClient};
+use crate::provider::manifest::Manifest;
+use crate::ETag;
+use futures::Future;
+use reqwest::{header, r#async::Response as ReqwestResponse};
+use serde::{Deserialize, Serialize};
+use std::error;
+use std::fmt;
+
+/// The number of releases requested per page, which is Gitea's default maximum.
+const PER_PAGE: u32 = 50;
+
+pub type Error = http::Error<RequestError>;
+
+pub type Response<T> = http::Response<T>;
+
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub id: u64,
+    pub tag_name: String,
+    pub draft: bool,
+    pub prerelease: bool,
+    pub created_at: String,
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Asset {
+    pub id: u64,
+    pub name: String,
+    pub uuid: String,
+    pub browser_download_url: String,
+    pub size: u64,
+    pub download_count: u64,
+    pub created_at: String,
+}
+
+pub struct Client {
+    inner: HttpClient<RequestError>,
+}
+
+impl Client {
+    /// Builds a client which authorizes with an access token.
+    ///
+    /// The token is sent as `Authorization: token <token>`, which is the header documented by
+    /// both Gitea and Forgejo and happens to be the same as GitHub's.
+    pub fn build<D, O>(domain: D, oauth_token: O) -> Result<Self, Error>
+    where
+        D: AsRef<str>,
+        O: AsRef<str>,
+    {
+        let mut inner = HttpClient::build(
+            &format!("https://{}/api/v1", domain.as_ref()),
+            "application/json",
+        )?;
+        inner.set_authorization(Some(
+            header::HeaderValue::from_str(&format!("token {}", oauth_token.as_ref()))
+                .map_err(|err| Error::InvalidHeaderValue("authorization", err))?,
+        ));
+
+        Ok(Self { inner })
+    }
+
+    /// Lists the releases of a repo, following every page of results.
+    pub fn releases<O, N>(
+        &self,
+        owner: O,
+        name: N,
+        etag: Option<&ETag>,
+    ) -> impl Future<Item = Option<Response<Vec<Release>>>, Error = Error>
+    where
+        O: AsRef<str>,
+        N: AsRef<str>,
+    {
+        self.inner.get_pages(
+            self.inner.url(
+                format!("/repos/{}/{}/releases", owner.as_ref(), name.as_ref()),
+                Some(format!("limit={}", PER_PAGE)),
+            ),
+            etag,
+            None,
+        )
+    }
+
+    pub fn latest_release<O, N>(
+        &self,
+        owner: O,
+        name: N,
+        etag: Option<&ETag>,
+    ) -> impl Future<Item = Option<Response<Release>>, Error = Error>
+    where
+        O: AsRef<str>,
+        N: AsRef<str>,
+    {
+        self.inner.get(
+            self.inner.url(
+                format!(
+                    "/repos/{}/{}/releases/latest",
+                    owner.as_ref(),
+                    name.as_ref()
+                ),
+                None::<&str>,
+            ),
+            etag,
+        )
+    }
+
+    pub fn manifest<U, A>(
+        &self,
+        url: U,
+        asset_name: A,
+    ) -> impl Future<Item = Manifest, Error = Error>
+    where
+        U: Into<String>,
+        A: Into<String>,
+    {
+        self.inner
+            .get_body(url.into())
+            .and_then(|bytes| Manifest::from_bytes(asset_name, bytes).map_err(Error::from))
+    }
+
+    /// Requests the bytes of an asset, passing on a `Range` header if given.
+    pub fn download<U: Into<String>>(
+        &self,
+        url: U,
+        range: Option<&str>,
+    ) -> impl Future<Item = ReqwestResponse, Error = Error> {
+        self.inner.get_stream(url.into(), range)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RequestError {
+    pub message: String,
+    pub url: Option<String>,
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "request error: {}", self.message)
+    }
+}
+
+impl error::Error for RequestError {}
+
+impl ErrorBody for RequestError {
+    fn message(&self) -> &str {
+        &self.message
+    }
+}
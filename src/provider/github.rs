@@ -1,25 +1,25 @@
-use super::Error;
-use crate::{Asset, ETag, Release, Repo, Target};
+use super::conditional::{missing_as_none, ConditionalUpdate, Resource};
+use super::http;
+use super::infer::TargetInference;
+use super::manifest;
+use super::proxy::AssetStream;
+use super::rate_limit::Budget;
+use super::repo_map::RepoMap;
+use super::{parse_timestamp, Error, Pagination, ReleaseOptions, ReleaseSource};
+use crate::{Asset, ETag, Release, Repo};
 use actix_web::http::{HttpTryFrom, Uri};
-use futures::{
-    future::{self, Either},
-    Future,
-};
-use log::info;
-use std::collections::HashMap;
+use futures::{future, Future};
+use log::warn;
 use std::fmt;
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 
+pub mod auth;
 pub mod client;
 
-const NO_ETAG: &str = "<none>";
-const MANIFEST_EXT: &str = ".manifest.txt";
-
-type RepoMap = Arc<HashMap<String, HashMap<String, RwLock<Arc<Repo>>>>>;
-
 pub struct GitHub {
     domain: String,
     client: Arc<client::Client>,
+    pagination: Pagination,
     repos: RepoMap,
 }
 
@@ -39,77 +39,87 @@ impl fmt::Debug for GitHub {
 }
 
 impl GitHub {
-    pub fn build<S, O, R>(domain: S, oauth_token: O, iter: R) -> Result<Self, Error>
+    pub fn build<S, R>(
+        domain: S,
+        auth: auth::Auth,
+        pagination: Pagination,
+        iter: R,
+    ) -> Result<Self, Error>
     where
         S: Into<String>,
-        O: AsRef<str>,
         R: IntoIterator<Item = Repo>,
     {
         let domain = domain.into();
+        if let auth::Auth::Anonymous = auth {
+            warn!(
+                "github registry entry has no credentials, so only public repos can be read and \
+                the unauthenticated rate limit of 60 requests per hour applies; domain={}",
+                domain
+            );
+        }
         let client = match domain.as_str() {
-            "github.com" => Arc::new(client::Client::build(oauth_token)?),
+            "github.com" => Arc::new(client::Client::build(auth, pagination)?),
             enterprise => Arc::new(client::Client::build_for_enterprise(
-                enterprise,
-                oauth_token,
+                enterprise, auth, pagination,
             )?),
         };
-        let mut repos = HashMap::new();
-        for repo in iter {
-            repos
-                .entry(repo.owner().to_string())
-                .or_insert_with(HashMap::new)
-                .insert(repo.name().to_string(), RwLock::new(Arc::new(repo)));
-        }
-        let repos = Arc::new(repos);
+        let repos = RepoMap::new(iter);
 
         Ok(GitHub {
             domain,
             client,
+            pagination,
             repos,
         })
     }
+}
 
-    pub fn domain(&self) -> &str {
+impl ReleaseSource for GitHub {
+    fn domain(&self) -> &str {
         &self.domain
     }
 
-    pub fn repos<'a>(&'a self) -> impl Iterator<Item = Arc<Repo>> + 'a {
-        self.repos
-            .values()
-            .map(|i| i.values().map(|r| r.read().expect("lock poisoned").clone()))
-            .flatten()
+    fn repos<'a>(&'a self) -> Box<dyn Iterator<Item = Arc<Repo>> + 'a> {
+        Box::new(self.repos.repos())
     }
 
-    pub fn repo<S, T>(&self, owner: S, name: T) -> Option<Arc<Repo>>
-    where
-        S: AsRef<str>,
-        T: AsRef<str>,
-    {
-        self.repos
-            .get(owner.as_ref())
-            .and_then(|o| o.get(name.as_ref()))
-            .map(|r| r.read().expect("lock poisoned").clone())
+    fn repo(&self, owner: &str, name: &str) -> Option<Arc<Repo>> {
+        self.repos.repo(owner, name)
     }
 
-    pub fn update_repo<O, N>(&self, owner: O, name: N) -> impl Future<Item = (), Error = Error>
-    where
-        O: Into<String>,
-        N: Into<String>,
-    {
-        let owner: Arc<str> = owner.into().into();
-        let name: Arc<str> = name.into().into();
+    fn restore_repo(&self, snapshot: Repo) -> Result<(), Error> {
+        self.repos.restore(snapshot)
+    }
+
+    fn pagination(&self) -> Option<Pagination> {
+        Some(self.pagination)
+    }
+
+    fn rate_limit(&self) -> Option<Budget> {
+        Some(self.client.rate_limit())
+    }
+
+    fn update_repo(&self, owner: &str, name: &str) -> Box<dyn Future<Item = (), Error = Error>> {
+        let owner: Arc<str> = owner.into();
+        let name: Arc<str> = name.into();
         let domain: Arc<str> = self.domain.clone().into();
 
-        let (releases_etag, latest_etag) = {
+        let (releases_etag, latest_etag, options) = {
             let repo = match self.repo(&owner, &name) {
                 Some(repo) => repo,
-                None => return Either::A(future::err(Error::RepoNotFound)),
+                None => return Box::new(future::err(Error::RepoNotFound)),
             };
 
-            (repo.releases_etag().cloned(), repo.latest_etag().cloned())
+            (
+                repo.releases_etag().cloned(),
+                repo.latest_etag().cloned(),
+                ReleaseOptions::for_repo(&repo),
+            )
         };
 
-        Either::B(
+        let repos = self.repos.clone();
+
+        Box::new(
             update_releases(
                 self.client.clone(),
                 self.repos.clone(),
@@ -117,16 +127,35 @@ impl GitHub {
                 owner.clone(),
                 name.clone(),
                 releases_etag,
+                options,
             )
             .join(update_latest(
                 self.client.clone(),
                 self.repos.clone(),
                 domain,
-                owner,
-                name,
+                owner.clone(),
+                name.clone(),
                 latest_etag,
             ))
-            .and_then(|_| future::ok(())),
+            .and_then(move |_| repos.mark_updated(&owner, &name)),
+        )
+    }
+
+    fn fetch_asset(
+        &self,
+        asset: &Asset,
+        range: Option<String>,
+    ) -> Box<dyn Future<Item = AssetStream, Error = Error>> {
+        let uri = match asset.proxy_uri() {
+            Some(uri) => uri.to_string(),
+            None => return Box::new(future::err(Error::ProxyUnsupported)),
+        };
+
+        Box::new(
+            self.client
+                .download(uri, range.as_deref())
+                .map(AssetStream::from_response)
+                .map_err(Error::from),
         )
     }
 }
@@ -138,50 +167,32 @@ fn update_releases(
     owner: Arc<str>,
     name: Arc<str>,
     etag: Option<ETag>,
+    options: ReleaseOptions,
 ) -> impl Future<Item = (), Error = Error> {
+    let indexed = repos.repo(&owner, &name);
+    let update = ConditionalUpdate::new(
+        repos,
+        domain,
+        owner.clone(),
+        name.clone(),
+        Resource::Releases,
+        etag,
+    );
+
     client
-        .releases(owner.clone(), name.clone(), etag.as_ref())
-        .map_err(|err| Error::Client(Box::new(err)))
-        .and_then(move |response| match response {
-            None => {
-                info!(
-                    "releases not modified; domain={}, repo={}/{}, etag={}",
-                    &domain,
-                    &owner,
-                    &name,
-                    etag.as_ref().map(|e| e.as_ref()).unwrap_or_else(|| NO_ETAG)
-                );
-
-                Either::A(future::ok(()))
-            }
-            Some(response) => {
-                let (next_etag, releases) = response.into_parts();
-
-                Either::B(
-                    process_releases(client, releases, owner.clone(), name.clone()).and_then(
-                        move |releases| match repo_mut(repos, &owner, &name, |repo| {
-                            repo.set_releases_etag(next_etag.as_ref().cloned());
+        .releases(owner.clone(), name.clone(), update.etag())
+        .map_err(Error::from)
+        .and_then(move |response| {
+            update.apply(response, move |releases| {
+                process_releases(client, releases, indexed, owner, name, options).map(
+                    |(releases, latest_prerelease)| {
+                        move |repo: &mut Repo| {
                             repo.set_releases(releases);
-                        }) {
-                            Ok(_) => {
-                                info!(
-                                    "releases updated; domain={}, repo={}/{}, next_etag={}",
-                                    &domain,
-                                    &owner,
-                                    &name,
-                                    next_etag
-                                        .as_ref()
-                                        .map(|e| e.as_ref())
-                                        .unwrap_or_else(|| NO_ETAG)
-                                );
-
-                                future::ok(())
-                            }
-                            Err(err) => future::err(err),
-                        },
-                    ),
+                            repo.set_latest_prerelease(latest_prerelease);
+                        }
+                    },
                 )
-            }
+            })
         })
 }
 
@@ -193,94 +204,102 @@ fn update_latest(
     name: Arc<str>,
     etag: Option<ETag>,
 ) -> impl Future<Item = (), Error = Error> {
+    let update = ConditionalUpdate::new(
+        repos,
+        domain,
+        owner.clone(),
+        name.clone(),
+        Resource::Latest,
+        etag,
+    );
+
     client
-        .latest_release(owner.clone(), name.clone(), etag.as_ref())
-        .map_err(|err| match err {
-            client::Error::NotFound => Error::LatestNotFound,
-            err => Error::Client(Box::new(err)),
+        .latest_release(owner, name, update.etag())
+        .then(|result| {
+            missing_as_none(result, |err| {
+                matches!(err, client::Error::Http(http::Error::NotFound))
+            })
         })
-        .and_then(move |response| match response {
-            None => {
-                info!(
-                    "latest release not modified; domain={}, repo={}/{}, etag={}",
-                    &domain,
-                    &owner,
-                    &name,
-                    etag.as_ref().map(|e| e.as_ref()).unwrap_or_else(|| NO_ETAG)
-                );
-
-                future::ok(())
-            }
-            Some(response) => {
-                let (next_etag, latest) = response.into_parts();
-
-                match repo_mut(repos, &owner, &name, |repo| {
-                    repo.set_latest_etag(next_etag.as_ref().cloned());
-                    repo.set_latest_release(Some(latest.tag_name));
-                }) {
-                    Ok(_) => {
-                        info!(
-                            "latest release updated; domain={}, repo={}/{}, next_etag={}",
-                            &domain,
-                            &owner,
-                            &name,
-                            next_etag
-                                .as_ref()
-                                .map(|e| e.as_ref())
-                                .unwrap_or_else(|| NO_ETAG)
-                        );
-
-                        future::ok(())
-                    }
-                    Err(err) => future::err(err),
-                }
-            }
+        .map_err(Error::from)
+        .and_then(move |response| {
+            update.apply(response, |latest| {
+                future::ok(move |repo: &mut Repo| {
+                    repo.set_latest_release(latest.map(|latest| latest.tag_name))
+                })
+            })
         })
 }
 
 fn process_releases(
     client: Arc<client::Client>,
     releases: Vec<client::Release>,
+    indexed: Option<Arc<Repo>>,
     owner: Arc<str>,
     name: Arc<str>,
-) -> impl Future<Item = Vec<Release>, Error = Error> {
+    options: ReleaseOptions,
+) -> impl Future<Item = (Vec<Release>, Option<String>), Error = Error> {
     let filtered_releases = releases
         .into_iter()
-        .filter(|rel| !rel.draft && !rel.prerelease)
+        .filter(|rel| options.filter.includes(rel.draft, rel.prerelease))
         .collect::<Vec<_>>();
+    let latest_prerelease = filtered_releases
+        .iter()
+        .max_by(|a, b| a.created_at.cmp(&b.created_at))
+        .map(|rel| rel.tag_name.clone());
 
     let mut all_manifests = Vec::new();
     for release in &filtered_releases {
-        all_manifests.push(future::join_all(
-            release
-                .assets
-                .iter()
-                .filter(|asset| asset.name.ends_with(MANIFEST_EXT))
-                .map(|asset| {
-                    client
-                        .manifest(
-                            owner.clone(),
-                            name.clone(),
-                            asset.id,
-                            asset.name.trim_end_matches(MANIFEST_EXT).to_string(),
-                        )
-                        .map_err(|err| Error::Client(Box::new(err)))
+        let mut files = manifest::manifest_files(release.assets.iter().map(|a| a.name.as_str()));
+        let reused = manifest::reuse_checksums(
+            &mut files,
+            indexed
+                .as_ref()
+                .and_then(|repo| repo.release(&release.tag_name)),
+            |file_name, asset| {
+                release.assets.iter().any(|a| {
+                    a.name == file_name
+                        && asset.revision() == Some(a.updated_at.as_str())
+                        && asset
+                            .download_uri()
+                            .is_some_and(|uri| uri == a.browser_download_url.as_str())
                 })
-                .collect::<Vec<_>>(),
-        ));
+            },
+        );
+        all_manifests.push(
+            future::join_all(
+                release
+                    .assets
+                    .iter()
+                    .filter(|asset| files.contains(asset.name.as_str()))
+                    .map(|asset| {
+                        client
+                            .manifest(owner.clone(), name.clone(), asset.id, asset.name.clone())
+                            .map_err(Error::from)
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .map(move |mut manifests| {
+                manifests.push(reused);
+                manifests
+            }),
+        );
     }
 
-    future::join_all(all_manifests)
-        .and_then(move |all_manifests| convert_releases(filtered_releases, all_manifests))
+    future::join_all(all_manifests).and_then(move |all_manifests| {
+        convert_releases(filtered_releases, all_manifests, &options.inference, &name)
+            .map(|releases| (releases, latest_prerelease))
+    })
 }
 
 fn convert_releases(
     client_releases: Vec<client::Release>,
-    client_all_manifests: Vec<Vec<client::Manifest>>,
+    client_all_manifests: Vec<Vec<manifest::Manifest>>,
+    inference: &TargetInference,
+    repo_name: &str,
 ) -> Result<Vec<Release>, Error> {
     let mut releases = Vec::new();
     for (release, manifests) in client_releases.into_iter().zip(client_all_manifests) {
-        releases.push(convert_release(release, manifests)?);
+        releases.push(convert_release(release, manifests, inference, repo_name)?);
     }
 
     Ok(releases)
@@ -288,61 +307,49 @@ fn convert_releases(
 
 fn convert_release(
     release: client::Release,
-    manifests: Vec<client::Manifest>,
+    mut manifests: Vec<manifest::Manifest>,
+    inference: &TargetInference,
+    repo_name: &str,
 ) -> Result<Release, Error> {
-    let mut targets = HashMap::new();
-    for manifest in manifests {
-        for entry in manifest.entries {
-            let (entry_target, entry_asset) = (entry.target, entry.asset);
-
-            let target = targets
-                .entry(entry_target.clone())
-                .or_insert_with(|| Target::new(entry_target));
-            target.push_asset(Asset::new(
-                manifest.name.clone(),
-                uri_for_asset(&entry_asset, &release.assets)?,
-            ));
-        }
+    if !manifest::has_targets(&manifests) {
+        manifests
+            .extend(inference.manifests(release.assets.iter().map(|a| a.name.as_str()), repo_name));
     }
+    let targets = manifest::targets_from_manifests(manifests, |name, asset| {
+        asset_for_entry(name, asset, &release.assets)
+    })?;
 
     let mut converted = Release::from(release);
-    converted.set_targets(
-        targets
-            .into_iter()
-            .map(|(_, value)| value)
-            .collect::<Vec<_>>(),
-    );
+    converted.set_targets(targets);
 
     Ok(converted)
 }
 
-fn repo_mut<F>(repos: RepoMap, owner: &str, name: &str, update: F) -> Result<(), Error>
-where
-    F: FnOnce(&mut Repo),
-{
-    let mut repo = repos
-        .get(owner)
-        .and_then(|o| o.get(name))
-        .map(|r| r.write().expect("lock poisoned"))
-        .ok_or(Error::RepoNotFound)?;
+fn asset_for_entry(name: String, gh_name: &str, assets: &[client::Asset]) -> Result<Asset, Error> {
+    let client_asset = assets.iter().find(|a| a.name == gh_name).ok_or_else(|| {
+        http::Error::<client::RequestError>::MissingResponseField("browser_download_url")
+    })?;
+    let uri_str = &client_asset.browser_download_url;
+    let uri = Uri::try_from(uri_str).map_err(|err| Error::InvalidUri(uri_str.to_string(), err))?;
 
-    update(Arc::make_mut(&mut repo));
+    let api_uri = Uri::try_from(&client_asset.url)
+        .map_err(|err| Error::InvalidUri(client_asset.url.to_string(), err))?;
 
-    Ok(())
-}
-
-fn uri_for_asset(gh_name: &str, assets: &[client::Asset]) -> Result<Uri, Error> {
-    let uri_str = assets
-        .iter()
-        .find(|a| a.name == gh_name)
-        .map(|a| &a.browser_download_url)
-        .ok_or_else(|| client::Error::MissingResponseField("browser_download_url"))?;
+    let mut asset = Asset::new(name, uri);
+    asset.set_size(Some(client_asset.size));
+    asset.set_proxy_uri(Some(api_uri));
+    asset.set_revision(Some(client_asset.updated_at.as_str()));
+    asset.set_content_type(Some(client_asset.content_type.as_str()));
 
-    Uri::try_from(uri_str).map_err(|err| Error::InvalidUri(uri_str.to_string(), err))
+    Ok(asset)
 }
 
 impl From<client::Release> for Release {
     fn from(cr: client::Release) -> Self {
-        Release::new(cr.id, cr.tag_name)
+        let mut release = Release::new(cr.id, cr.tag_name);
+        release.set_prerelease(cr.prerelease);
+        release.set_created(parse_timestamp(&cr.created_at));
+
+        release
     }
 }
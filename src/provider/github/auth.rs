@@ -0,0 +1,132 @@
+use super::client::Error;
+use crate::provider::rate_limit::unix_now;
+use ring::rand::SystemRandom;
+use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
+use serde_json::json;
+use std::fmt;
+use std::sync::Arc;
+
+/// How far back a JWT's issue time is set, to allow for clock drift between us and GitHub.
+const JWT_CLOCK_DRIFT: u64 = 60;
+
+/// How long a JWT is valid for, just short of GitHub's maximum of 10 minutes.
+const JWT_LIFETIME: u64 = 9 * 60;
+
+/// How requests to the GitHub API are authenticated.
+#[derive(Clone, Debug)]
+pub enum Auth {
+    /// No credentials, which only gives access to public repos under a much lower rate limit.
+    Anonymous,
+    /// A personal access token or OAuth token.
+    Token(String),
+    /// A GitHub App installation, whose access tokens are minted as they are needed.
+    App(App),
+}
+
+/// The credentials of a GitHub App installation.
+#[derive(Clone)]
+pub struct App {
+    app_id: u64,
+    installation_id: u64,
+    key: Arc<RsaKeyPair>,
+}
+
+impl App {
+    /// Builds the credentials of an installation from the app's PEM encoded private key, as
+    /// downloaded from GitHub.
+    pub fn from_pem(app_id: u64, installation_id: u64, pem: &str) -> Result<Self, Error> {
+        let (label, der) = decode_pem(pem)?;
+        let key = match label {
+            "RSA PRIVATE KEY" => RsaKeyPair::from_der(&der),
+            "PRIVATE KEY" => RsaKeyPair::from_pkcs8(&der),
+            label => {
+                return Err(Error::InvalidPrivateKey(format!(
+                    "unsupported pem label: {}",
+                    label
+                )))
+            }
+        }
+        .map_err(|err| Error::InvalidPrivateKey(err.to_string()))?;
+
+        Ok(App {
+            app_id,
+            installation_id,
+            key: Arc::new(key),
+        })
+    }
+
+    pub fn app_id(&self) -> u64 {
+        self.app_id
+    }
+
+    pub fn installation_id(&self) -> u64 {
+        self.installation_id
+    }
+
+    /// Mints a JSON Web Token which authenticates as the app, for exchanging for an installation
+    /// access token.
+    pub fn jwt(&self) -> Result<String, Error> {
+        let now = unix_now();
+        let header = json!({ "alg": "RS256", "typ": "JWT" });
+        let claims = json!({
+            "iat": now - JWT_CLOCK_DRIFT,
+            "exp": now + JWT_LIFETIME,
+            "iss": self.app_id,
+        });
+        let message = format!(
+            "{}.{}",
+            base64_url(header.to_string().as_bytes()),
+            base64_url(claims.to_string().as_bytes())
+        );
+
+        let mut signature = vec![0; self.key.public_modulus_len()];
+        self.key
+            .sign(
+                &RSA_PKCS1_SHA256,
+                &SystemRandom::new(),
+                message.as_bytes(),
+                &mut signature,
+            )
+            .map_err(|_| Error::Signing)?;
+
+        Ok(format!("{}.{}", message, base64_url(&signature)))
+    }
+}
+
+impl fmt::Debug for App {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("App")
+            .field("app_id", &self.app_id)
+            .field("installation_id", &self.installation_id)
+            .finish()
+    }
+}
+
+/// Returns the label and decoded contents of the first block of a PEM document.
+fn decode_pem(pem: &str) -> Result<(&str, Vec<u8>), Error> {
+    let invalid = |reason: &str| Error::InvalidPrivateKey(reason.to_string());
+
+    let begin = pem
+        .find("-----BEGIN ")
+        .ok_or_else(|| invalid("missing pem header"))?;
+    let rest = &pem[begin + "-----BEGIN ".len()..];
+    let label_end = rest
+        .find("-----")
+        .ok_or_else(|| invalid("malformed pem header"))?;
+    let label = &rest[..label_end];
+    let body = &rest[label_end + "-----".len()..];
+    let end = body
+        .find(&format!("-----END {}-----", label))
+        .ok_or_else(|| invalid("missing pem footer"))?;
+    let body = body[..end]
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>();
+    let der = base64::decode(&body).map_err(|err| Error::InvalidPrivateKey(err.to_string()))?;
+
+    Ok((label, der))
+}
+
+fn base64_url(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
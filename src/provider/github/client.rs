@@ -1,38 +1,27 @@
+use super::auth::{App, Auth};
+use crate::provider::http::{self, ErrorBody, HttpClient};
+use crate::provider::manifest::{Manifest, ParseError};
+use crate::provider::rate_limit::{unix_now, Budget, RateLimit};
+use crate::provider::Pagination;
 use crate::ETag;
+use chrono::DateTime;
 use futures::{
-    future::{self, Either},
-    Future, Stream,
+    future::{self, Either, Shared},
+    Future,
 };
-use log::{debug, error, warn};
-use reqwest::{
-    header,
-    r#async::{Chunk, Client as ReqwestClient, Decoder, Response as ReqwestResponse},
-    StatusCode,
-};
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use log::info;
+use reqwest::{header, r#async::Response as ReqwestResponse};
+use serde::{Deserialize, Serialize};
 use std::error;
 use std::fmt;
-use std::str::{self, FromStr};
+use std::sync::{Arc, Mutex};
 
 const DEFAULT_DOMAIN: &str = "api.github.com";
 
-#[derive(Debug)]
-pub struct Response<T> {
-    etag: Option<ETag>,
-    payload: T,
-}
+/// How long before it expires an installation access token is replaced, in seconds.
+const TOKEN_REFRESH_MARGIN: u64 = 5 * 60;
 
-impl<T> AsRef<T> for Response<T> {
-    fn as_ref(&self) -> &T {
-        &self.payload
-    }
-}
-
-impl<T> Response<T> {
-    pub fn into_parts(self) -> (Option<ETag>, T) {
-        (self.etag, self.payload)
-    }
-}
+pub type Response<T> = http::Response<T>;
 
 #[derive(Debug, Deserialize)]
 pub struct Release {
@@ -55,83 +44,46 @@ pub struct Asset {
     pub size: u64,
     pub download_count: u64,
     pub created_at: String,
+    pub updated_at: String,
 }
 
-#[derive(Debug)]
-pub struct Manifest {
-    pub name: String,
-    pub entries: Vec<ManifestEntry>,
+pub struct Client {
+    inner: HttpClient<RequestError>,
+    credentials: Credentials,
+    pagination: Pagination,
 }
 
-impl Manifest {
-    pub fn from_bytes<N, I>(name: N, input: I) -> Result<Self, Error>
-    where
-        N: Into<String>,
-        I: AsRef<[u8]>,
-    {
-        let mut entries = Vec::new();
-        for line in str::from_utf8(input.as_ref())?.lines() {
-            entries.push(line.parse()?);
-        }
-
-        Ok(Manifest {
-            name: name.into(),
-            entries,
-        })
+impl Client {
+    pub fn build(auth: Auth, pagination: Pagination) -> Result<Self, Error> {
+        Self::build_for_domain(DEFAULT_DOMAIN.to_string(), auth, pagination)
     }
-}
 
-#[derive(Debug)]
-pub struct ManifestEntry {
-    pub target: String,
-    pub asset: String,
-}
-
-impl FromStr for ManifestEntry {
-    type Err = ManifestEntryParseError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let fields = s.split_ascii_whitespace().collect::<Vec<_>>();
-        let num_fields = fields.len();
-
-        if num_fields == 1 {
-            Err(ManifestEntryParseError(
-                "missing whitespace delimiter between fields",
-            ))
-        } else if num_fields > 2 {
-            Err(ManifestEntryParseError("more than two fields"))
-        } else if num_fields != 2 {
-            unreachable!("invalid number of fields");
-        } else {
-            Ok(ManifestEntry {
-                target: fields[0].to_string(),
-                asset: fields[1].to_string(),
-            })
-        }
+    pub fn build_for_enterprise<D: AsRef<str>>(
+        domain: D,
+        auth: Auth,
+        pagination: Pagination,
+    ) -> Result<Self, Error> {
+        Self::build_for_domain(format!("{}/api/v3", domain.as_ref()), auth, pagination)
     }
-}
 
-pub struct Client {
-    inner: HttpClient,
-}
+    fn build_for_domain(domain: String, auth: Auth, pagination: Pagination) -> Result<Self, Error> {
+        let mut inner = HttpClient::build(
+            &format!("https://{}", domain),
+            "application/vnd.github.v3+json",
+        )?;
+        inner.set_rate_limit(Some(RateLimit::new()));
 
-impl Client {
-    pub fn build<O: AsRef<str>>(oauth_token: O) -> Result<Self, Error> {
         Ok(Self {
-            inner: HttpClient::build(oauth_token)?,
-        })
-    }
-
-    pub fn build_for_enterprise<D, O>(domain: D, oauth_token: O) -> Result<Self, Error>
-    where
-        D: AsRef<str>,
-        O: AsRef<str>,
-    {
-        Ok(Self {
-            inner: HttpClient::build_for_enterprise(domain, oauth_token)?,
+            inner,
+            credentials: Credentials::new(auth)?,
+            pagination,
         })
     }
 
+    /// Lists the releases of a repo, following every page of results.
+    ///
+    /// The entity tag only applies to the first page, so when it is not modified no further
+    /// pages are requested and `None` is returned.
     pub fn releases<O, N>(
         &self,
         owner: O,
@@ -142,11 +94,18 @@ impl Client {
         O: AsRef<str>,
         N: AsRef<str>,
     {
-        self.inner.get(
+        let url = self.inner.url(
             format!("/repos/{}/{}/releases", owner.as_ref(), name.as_ref()),
-            None::<&str>,
-            etag,
-        )
+            Some(format!("per_page={}", self.pagination.per_page.min(100))),
+        );
+        let etag = etag.cloned();
+        let max_releases = self.pagination.max_releases;
+
+        self.authorized().and_then(move |inner| {
+            inner
+                .get_pages(url, etag.as_ref(), max_releases)
+                .map_err(Error::from)
+        })
     }
 
     pub fn latest_release<O, N>(
@@ -159,15 +118,18 @@ impl Client {
         O: AsRef<str>,
         N: AsRef<str>,
     {
-        self.inner.get(
+        let url = self.inner.url(
             format!(
                 "/repos/{}/{}/releases/latest",
                 owner.as_ref(),
                 name.as_ref()
             ),
             None::<&str>,
-            etag,
-        )
+        );
+        let etag = etag.cloned();
+
+        self.authorized()
+            .and_then(move |inner| inner.get(url, etag.as_ref()).map_err(Error::from))
     }
 
     pub fn manifest<O, N, A>(
@@ -182,162 +144,206 @@ impl Client {
         N: AsRef<str>,
         A: Into<String>,
     {
-        self.inner
-            .get_body(
-                format!(
-                    "/repos/{}/{}/releases/assets/{}",
-                    owner.as_ref(),
-                    name.as_ref(),
-                    asset_id
-                ),
-                None::<&str>,
-            )
-            .and_then(|bytes| Manifest::from_bytes(asset_name, bytes))
-    }
-}
-
-struct HttpClient {
-    inner: ReqwestClient,
-    domain: String,
-}
+        let url = self.inner.url(
+            format!(
+                "/repos/{}/{}/releases/assets/{}",
+                owner.as_ref(),
+                name.as_ref(),
+                asset_id
+            ),
+            None::<&str>,
+        );
 
-impl HttpClient {
-    fn build<O: AsRef<str>>(oauth_token: O) -> Result<Self, Error> {
-        Ok(Self {
-            inner: reqwest_client(oauth_token)?,
-            domain: DEFAULT_DOMAIN.to_string(),
-        })
+        self.authorized()
+            .and_then(move |inner| inner.get_body(url).map_err(Error::from))
+            .and_then(|bytes| Manifest::from_bytes(asset_name, bytes).map_err(Error::from))
     }
 
-    fn build_for_enterprise<D, O>(domain: D, oauth_token: O) -> Result<Self, Error>
-    where
-        D: AsRef<str>,
-        O: AsRef<str>,
-    {
-        Ok(Self {
-            inner: reqwest_client(oauth_token)?,
-            domain: format!("{}/api/v3", domain.as_ref()),
-        })
+    /// Returns the rate limit budget, as last reported by the API.
+    pub fn rate_limit(&self) -> Budget {
+        self.inner
+            .rate_limit()
+            .map(RateLimit::budget)
+            .unwrap_or_default()
     }
 
-    fn get<P, Q, T>(
+    /// Requests the bytes of an asset, passing on a `Range` header if given.
+    pub fn download<U: Into<String>>(
         &self,
-        path: P,
-        query: Option<Q>,
-        etag: Option<&ETag>,
-    ) -> impl Future<Item = Option<Response<T>>, Error = Error>
-    where
-        P: AsRef<str>,
-        Q: AsRef<str>,
-        T: DeserializeOwned,
-    {
-        let mut req = self.inner.get(&self.url(path, query));
-        if let Some(etag) = etag {
-            let val = match header::HeaderValue::from_str(etag.as_ref()) {
-                Ok(val) => val,
-                Err(err) => return Either::A(future::err(Error::InvalidHeaderValue("etag", err))),
-            };
-            req = req.header(header::IF_NONE_MATCH, val);
-        }
+        url: U,
+        range: Option<&str>,
+    ) -> impl Future<Item = ReqwestResponse, Error = Error> {
+        let url = url.into();
+        let range = range.map(str::to_string);
+
+        self.authorized()
+            .and_then(move |inner| inner.get_stream(url, range.as_deref()).map_err(Error::from))
+    }
 
-        Either::B(req.send().map_err(Error::Request).and_then(|mut response| {
-            debug!("response: {:?}", response);
-            if response.status() == StatusCode::NOT_MODIFIED {
-                Either::A(future::ok(None))
-            } else if response.status().is_success() {
-                let etag = response_etag(&response);
-
-                Either::B(Either::A(
-                    response
-                        .json()
-                        .map_err(Error::Deserialize)
-                        .map(|t| Some(Response { etag, payload: t })),
+    /// Returns the HTTP client, authorized with the client's credentials unless it is anonymous.
+    ///
+    /// For a GitHub App, the installation access token is reused until it is about to expire, and
+    /// is then replaced by a new one. Requests which need a new token while one is being minted
+    /// wait for that one rather than minting their own.
+    fn authorized(&self) -> impl Future<Item = HttpClient<RequestError>, Error = Error> {
+        let (app, state) = match self.credentials {
+            Credentials::Anonymous => return Either::A(future::ok(self.inner.clone())),
+            Credentials::Token(ref authorization) => {
+                return Either::A(future::ok(
+                    self.inner.with_authorization(Some(authorization.clone())),
                 ))
-            } else if response.status() == StatusCode::NOT_FOUND {
-                Either::B(Either::B(Either::A(future::err(Error::NotFound))))
-            } else {
-                Either::B(Either::B(Either::B(
-                    response
-                        .json()
-                        .map_err(Error::Deserialize)
-                        .and_then(|err| future::err(Error::Api(err))),
-                )))
             }
-        }))
-    }
+            Credentials::App { ref app, ref token } => (app, token),
+        };
+
+        let pending = {
+            let mut state_guard = state.lock().expect("lock poisoned");
+            if let Some(ref token) = state_guard.token {
+                if token.expires_at > unix_now() + TOKEN_REFRESH_MARGIN {
+                    return Either::A(future::ok(
+                        self.inner
+                            .with_authorization(Some(token.authorization.clone())),
+                    ));
+                }
+            }
 
-    fn get_body<P, Q>(&self, path: P, query: Option<Q>) -> impl Future<Item = Chunk, Error = Error>
-    where
-        P: AsRef<str>,
-        Q: AsRef<str>,
-    {
-        self.inner
-            .get(&self.url(path, query))
-            .header(
-                header::ACCEPT,
-                header::HeaderValue::from_static("application/octet-stream"),
-            )
-            .send()
-            .map_err(Error::Request)
-            .and_then(|mut response| {
-                if response.status().is_success() {
-                    let body = std::mem::replace(response.body_mut(), Decoder::empty());
-                    Either::A(body.concat2().map_err(Error::Response))
-                } else {
-                    Either::B(
-                        response
-                            .json()
-                            .map_err(Error::Deserialize)
-                            .and_then(|err| Err(Error::Api(err))),
-                    )
+            match state_guard.pending {
+                Some(ref pending) => pending.clone(),
+                None => {
+                    let pending = match self.mint_token(app, state.clone()) {
+                        Ok(pending) => pending,
+                        Err(err) => return Either::A(future::err(err)),
+                    };
+                    state_guard.pending = Some(pending.clone());
+                    pending
                 }
+            }
+        };
+        let inner = self.inner.clone();
+
+        Either::B(
+            pending
+                .map(move |token| inner.with_authorization(Some(token.authorization.clone())))
+                .map_err(|err| Error::InstallationToken((*err).clone())),
+        )
+    }
+
+    /// Starts minting an installation access token, which is stored in `state` once minted.
+    fn mint_token(&self, app: &App, state: Arc<Mutex<TokenState>>) -> Result<PendingToken, Error> {
+        let jwt = app.jwt()?;
+        let bearer = header::HeaderValue::from_str(&format!("Bearer {}", jwt))
+            .map_err(|err| http::Error::InvalidHeaderValue("authorization", err))?;
+        let installation_id = app.installation_id();
+        let url = self.inner.url(
+            format!("/app/installations/{}/access_tokens", installation_id),
+            None::<&str>,
+        );
+
+        let minted = self
+            .inner
+            .post::<AccessTokenResponse>(url, bearer)
+            .map_err(Error::from)
+            .and_then(move |response| {
+                let expires_at = DateTime::parse_from_rfc3339(&response.expires_at)
+                    .map_err(|_| http::Error::MissingResponseField("expires_at"))?
+                    .timestamp() as u64;
+                let authorization = authorization_value(&response.token)?;
+                info!(
+                    "minted github app installation token; installation_id={}, expires_at={}",
+                    installation_id, response.expires_at
+                );
+
+                Ok(InstallationToken {
+                    authorization,
+                    expires_at,
+                })
             })
+            .then(move |result| {
+                let mut state = state.lock().expect("lock poisoned");
+                state.pending = None;
+                match result {
+                    Ok(token) => {
+                        state.token = Some(token.clone());
+                        Ok(token)
+                    }
+                    Err(err) => Err(Arc::new(err)),
+                }
+            });
+        let minted: Box<dyn Future<Item = InstallationToken, Error = Arc<Error>> + Send> =
+            Box::new(minted);
+
+        Ok(minted.shared())
     }
+}
 
-    fn url<P, Q>(&self, path: P, query: Option<Q>) -> String
-    where
-        P: AsRef<str>,
-        Q: AsRef<str>,
-    {
-        let mut url = format!("https://{}{}", &self.domain, path.as_ref());
-        if let Some(query) = query {
-            url.push_str("?");
-            url.push_str(query.as_ref());
-        }
+/// The credentials which requests are authorized with.
+#[derive(Clone)]
+enum Credentials {
+    Anonymous,
+    Token(header::HeaderValue),
+    App {
+        app: App,
+        token: Arc<Mutex<TokenState>>,
+    },
+}
 
-        url
+impl Credentials {
+    fn new(auth: Auth) -> Result<Self, Error> {
+        match auth {
+            Auth::Anonymous => Ok(Credentials::Anonymous),
+            Auth::Token(token) => Ok(Credentials::Token(authorization_value(&token)?)),
+            Auth::App(app) => Ok(Credentials::App {
+                app,
+                token: Arc::new(Mutex::new(TokenState::default())),
+            }),
+        }
     }
 }
 
+/// The installation access token of a GitHub App, along with the request minting a new one while
+/// it is in flight.
+#[derive(Default)]
+struct TokenState {
+    token: Option<InstallationToken>,
+    pending: Option<PendingToken>,
+}
+
+/// A request for an installation access token, shared by every request which waits for it.
+type PendingToken = Shared<Box<dyn Future<Item = InstallationToken, Error = Arc<Error>> + Send>>;
+
+/// An installation access token of a GitHub App, as the value of an `Authorization` header.
+#[derive(Clone)]
+struct InstallationToken {
+    authorization: header::HeaderValue,
+    /// When the token expires, in seconds since the Unix epoch.
+    expires_at: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccessTokenResponse {
+    token: String,
+    expires_at: String,
+}
+
 #[derive(Debug)]
 pub enum Error {
-    Api(RequestError),
-    Builder(reqwest::Error),
-    Deserialize(reqwest::Error),
-    InvalidHeaderValue(&'static str, reqwest::header::InvalidHeaderValue),
-    Manifest(ManifestEntryParseError),
-    MissingResponseField(&'static str),
-    NotFound,
-    Request(reqwest::Error),
-    Response(reqwest::Error),
-    Utf8(str::Utf8Error),
+    Http(http::Error<RequestError>),
+    InstallationToken(Arc<Error>),
+    InvalidPrivateKey(String),
+    Signing,
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Error::Api(ref err) => err.fmt(f),
-            Error::Builder(ref err) => err.fmt(f),
-            Error::Deserialize(ref err) => err.fmt(f),
-            Error::InvalidHeaderValue(ref name, ref err) => {
-                write!(f, "valid header value for {}: {}", name, err)
+            Error::Http(ref err) => err.fmt(f),
+            Error::InstallationToken(ref err) => {
+                write!(f, "failed to mint github app installation token: {}", err)
+            }
+            Error::InvalidPrivateKey(ref reason) => {
+                write!(f, "invalid github app private key: {}", reason)
             }
-            Error::Manifest(ref err) => err.fmt(f),
-            Error::MissingResponseField(ref name) => write!(f, "missing reponse field: {}", name),
-            Error::NotFound => f.write_str("github repository not found"),
-            Error::Request(ref err) => err.fmt(f),
-            Error::Response(ref err) => err.fmt(f),
-            Error::Utf8(ref err) => err.fmt(f),
+            Error::Signing => f.write_str("failed to sign github app token"),
         }
     }
 }
@@ -345,45 +351,29 @@ impl fmt::Display for Error {
 impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
-            Error::Api(ref err) => err.source(),
-            Error::Builder(ref err) => err.source(),
-            Error::Deserialize(ref err) => err.source(),
-            Error::InvalidHeaderValue(_, ref err) => err.source(),
-            Error::Manifest(ref err) => err.source(),
-            Error::MissingResponseField(_) => None,
-            Error::NotFound => None,
-            Error::Request(ref err) => err.source(),
-            Error::Response(ref err) => err.source(),
-            Error::Utf8(ref err) => err.source(),
+            Error::Http(ref err) => err.source(),
+            Error::InstallationToken(ref err) => Some(err.as_ref()),
+            Error::InvalidPrivateKey(_) => None,
+            Error::Signing => None,
         }
     }
 }
 
-impl From<str::Utf8Error> for Error {
-    fn from(err: str::Utf8Error) -> Self {
-        Error::Utf8(err)
+impl From<http::Error<RequestError>> for Error {
+    fn from(err: http::Error<RequestError>) -> Self {
+        Error::Http(err)
     }
 }
 
-impl From<ManifestEntryParseError> for Error {
-    fn from(err: ManifestEntryParseError) -> Self {
-        Error::Manifest(err)
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Self {
+        Error::Http(http::Error::Manifest(err))
     }
 }
 
-#[derive(Debug)]
-pub struct ManifestEntryParseError(&'static str);
-
-impl fmt::Display for ManifestEntryParseError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str(self.0)
-    }
-}
-
-impl error::Error for ManifestEntryParseError {}
-
 #[derive(Debug, Deserialize, Serialize)]
 pub struct RequestError {
+    #[serde(default)]
     pub success: bool,
     pub message: String,
 }
@@ -400,42 +390,14 @@ impl fmt::Display for RequestError {
 
 impl error::Error for RequestError {}
 
-fn reqwest_client<O: AsRef<str>>(oauth_token: O) -> Result<ReqwestClient, Error> {
-    let mut headers = header::HeaderMap::new();
-    headers.insert(
-        header::ACCEPT,
-        header::HeaderValue::from_static("application/vnd.github.v3+json"),
-    );
-    headers.insert(
-        header::AUTHORIZATION,
-        header::HeaderValue::from_str(&format!("token {}", oauth_token.as_ref()))
-            .map_err(|err| Error::InvalidHeaderValue("authorization", err))?,
-    );
-
-    ReqwestClient::builder()
-        .default_headers(headers)
-        .build()
-        .map_err(Error::Builder)
+impl ErrorBody for RequestError {
+    fn message(&self) -> &str {
+        &self.message
+    }
 }
 
-fn response_etag(response: &ReqwestResponse) -> Option<ETag> {
-    match response.headers().get(header::ETAG) {
-        Some(header) => match header.to_str() {
-            Ok(s) => match s.parse() {
-                Ok(etag) => Some(etag),
-                Err(err) => {
-                    error!("etag header could not be parsed; err={}", err);
-                    None
-                }
-            },
-            Err(err) => {
-                error!("etag header was not utf8 clean; err={}", err);
-                None
-            }
-        },
-        None => {
-            warn!("etag header not found in response and was expected");
-            None
-        }
-    }
+/// Returns the value of an `Authorization` header for an access token.
+fn authorization_value(token: &str) -> Result<header::HeaderValue, Error> {
+    header::HeaderValue::from_str(&format!("token {}", token))
+        .map_err(|err| http::Error::InvalidHeaderValue("authorization", err).into())
 }
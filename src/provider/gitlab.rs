@@ -0,0 +1,252 @@
+use super::conditional::{ConditionalUpdate, Resource};
+use super::infer::TargetInference;
+use super::manifest;
+use super::proxy::AssetStream;
+use super::repo_map::RepoMap;
+use super::{parse_timestamp, release_id, Error, ReleaseSource};
+use crate::{Asset, ETag, Release, Repo};
+use actix_web::http::{HttpTryFrom, Uri};
+use futures::{future, Future};
+use std::fmt;
+use std::sync::Arc;
+
+pub mod client;
+
+pub struct GitLab {
+    domain: String,
+    client: Arc<client::Client>,
+    repos: RepoMap,
+}
+
+impl fmt::Display for GitLab {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "provider::gitlab({})", &self.domain)
+    }
+}
+
+impl fmt::Debug for GitLab {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GitLab")
+            .field("domain", &self.domain)
+            .field("repos", &self.repos)
+            .finish()
+    }
+}
+
+impl GitLab {
+    pub fn build<S, O, R>(domain: S, oauth_token: O, iter: R) -> Result<Self, Error>
+    where
+        S: Into<String>,
+        O: AsRef<str>,
+        R: IntoIterator<Item = Repo>,
+    {
+        let domain = domain.into();
+        let client = Arc::new(client::Client::build(&domain, oauth_token)?);
+        let repos = RepoMap::new(iter);
+
+        Ok(GitLab {
+            domain,
+            client,
+            repos,
+        })
+    }
+}
+
+impl ReleaseSource for GitLab {
+    fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    fn repos<'a>(&'a self) -> Box<dyn Iterator<Item = Arc<Repo>> + 'a> {
+        Box::new(self.repos.repos())
+    }
+
+    fn repo(&self, owner: &str, name: &str) -> Option<Arc<Repo>> {
+        self.repos.repo(owner, name)
+    }
+
+    fn restore_repo(&self, snapshot: Repo) -> Result<(), Error> {
+        self.repos.restore(snapshot)
+    }
+
+    /// Updates the releases of a repo.
+    ///
+    /// GitLab returns releases sorted by their release date, so the latest release is taken from
+    /// the same response rather than from a separate request.
+    fn update_repo(&self, owner: &str, name: &str) -> Box<dyn Future<Item = (), Error = Error>> {
+        let owner: Arc<str> = owner.into();
+        let name: Arc<str> = name.into();
+        let domain: Arc<str> = self.domain.clone().into();
+
+        let (releases_etag, inference) = match self.repo(&owner, &name) {
+            Some(repo) => (
+                repo.releases_etag().cloned(),
+                repo.target_inference().clone(),
+            ),
+            None => return Box::new(future::err(Error::RepoNotFound)),
+        };
+
+        let repos = self.repos.clone();
+
+        Box::new(
+            update_releases(
+                self.client.clone(),
+                self.repos.clone(),
+                domain,
+                owner.clone(),
+                name.clone(),
+                releases_etag,
+                inference,
+            )
+            .and_then(move |_| repos.mark_updated(&owner, &name)),
+        )
+    }
+
+    fn fetch_asset(
+        &self,
+        asset: &Asset,
+        range: Option<String>,
+    ) -> Box<dyn Future<Item = AssetStream, Error = Error>> {
+        let uri = match asset.proxy_uri() {
+            Some(uri) => uri.to_string(),
+            None => return Box::new(future::err(Error::ProxyUnsupported)),
+        };
+
+        Box::new(
+            self.client
+                .download(uri, range.as_deref())
+                .map(AssetStream::from_response)
+                .map_err(Error::from),
+        )
+    }
+}
+
+fn update_releases(
+    client: Arc<client::Client>,
+    repos: RepoMap,
+    domain: Arc<str>,
+    owner: Arc<str>,
+    name: Arc<str>,
+    etag: Option<ETag>,
+    inference: TargetInference,
+) -> impl Future<Item = (), Error = Error> {
+    let update = ConditionalUpdate::new(
+        repos,
+        domain,
+        owner.clone(),
+        name.clone(),
+        Resource::Releases,
+        etag,
+    );
+
+    client
+        .releases(owner, name.clone(), update.etag())
+        .map_err(Error::from)
+        .and_then(move |response| {
+            update.apply(response, move |releases| {
+                process_releases(client, releases, name, inference).map(|(releases, latest)| {
+                    move |repo: &mut Repo| {
+                        repo.set_releases(releases);
+                        repo.set_latest_prerelease(latest.clone());
+                        repo.set_latest_release(latest);
+                    }
+                })
+            })
+        })
+}
+
+fn process_releases(
+    client: Arc<client::Client>,
+    releases: Vec<client::Release>,
+    name: Arc<str>,
+    inference: TargetInference,
+) -> impl Future<Item = (Vec<Release>, Option<String>), Error = Error> {
+    let filtered_releases = releases
+        .into_iter()
+        .filter(|rel| !rel.upcoming_release)
+        .collect::<Vec<_>>();
+    let latest = filtered_releases.first().map(|rel| rel.tag_name.clone());
+
+    let mut all_manifests = Vec::new();
+    for release in &filtered_releases {
+        let files = manifest::manifest_files(release.assets.links.iter().map(|l| l.name.as_str()));
+        all_manifests.push(future::join_all(
+            release
+                .assets
+                .links
+                .iter()
+                .filter(|link| files.contains(link.name.as_str()))
+                .map(|link| {
+                    client
+                        .manifest(link.download_url().to_string(), link.name.clone())
+                        .map_err(Error::from)
+                })
+                .collect::<Vec<_>>(),
+        ));
+    }
+
+    future::join_all(all_manifests).and_then(move |all_manifests| {
+        convert_releases(filtered_releases, all_manifests, &inference, &name)
+            .map(|releases| (releases, latest))
+    })
+}
+
+fn convert_releases(
+    client_releases: Vec<client::Release>,
+    client_all_manifests: Vec<Vec<manifest::Manifest>>,
+    inference: &TargetInference,
+    repo_name: &str,
+) -> Result<Vec<Release>, Error> {
+    let mut releases = Vec::new();
+    for (release, manifests) in client_releases.into_iter().zip(client_all_manifests) {
+        releases.push(convert_release(release, manifests, inference, repo_name)?);
+    }
+
+    Ok(releases)
+}
+
+fn convert_release(
+    release: client::Release,
+    mut manifests: Vec<manifest::Manifest>,
+    inference: &TargetInference,
+    repo_name: &str,
+) -> Result<Release, Error> {
+    if !manifest::has_targets(&manifests) {
+        manifests.extend(inference.manifests(
+            release.assets.links.iter().map(|l| l.name.as_str()),
+            repo_name,
+        ));
+    }
+    let targets = manifest::targets_from_manifests(manifests, |name, asset| {
+        Ok(Asset::new(
+            name,
+            uri_for_asset(asset, &release.assets.links)?,
+        ))
+    })?;
+
+    let mut converted = Release::from(release);
+    converted.set_targets(targets);
+
+    Ok(converted)
+}
+
+fn uri_for_asset(gl_name: &str, links: &[client::Link]) -> Result<Uri, Error> {
+    let uri_str = links
+        .iter()
+        .find(|l| l.name == gl_name)
+        .map(|l| l.download_url())
+        .ok_or_else(|| client::Error::MissingResponseField("url"))?;
+
+    Uri::try_from(uri_str).map_err(|err| Error::InvalidUri(uri_str.to_string(), err))
+}
+
+impl From<client::Release> for Release {
+    fn from(cr: client::Release) -> Self {
+        let mut release = Release::new(release_id(&cr.tag_name), &cr.tag_name);
+        release.set_created(parse_timestamp(
+            cr.released_at.as_ref().unwrap_or(&cr.created_at),
+        ));
+
+        release
+    }
+}
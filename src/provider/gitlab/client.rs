@@ -0,0 +1,148 @@
+use crate::provider::http::{self, ErrorBody, HttpClient};
+use crate::provider::manifest::Manifest;
+use crate::ETag;
+use futures::Future;
+use reqwest::{header, r#async::Response as ReqwestResponse};
+use serde::{Deserialize, Serialize};
+use std::error;
+use std::fmt;
+
+pub const DEFAULT_DOMAIN: &str = "gitlab.com";
+
+/// The number of releases requested per page, which is GitLab's maximum.
+const PER_PAGE: u32 = 100;
+
+pub type Error = http::Error<RequestError>;
+
+pub type Response<T> = http::Response<T>;
+
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub name: Option<String>,
+    pub created_at: String,
+    pub released_at: Option<String>,
+    #[serde(default)]
+    pub upcoming_release: bool,
+    pub assets: Assets,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Assets {
+    #[serde(default)]
+    pub links: Vec<Link>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Link {
+    pub id: u64,
+    pub name: String,
+    pub url: String,
+    pub direct_asset_url: Option<String>,
+    pub link_type: Option<String>,
+}
+
+impl Link {
+    /// Returns the most direct download URL for the link.
+    pub fn download_url(&self) -> &str {
+        self.direct_asset_url.as_ref().unwrap_or(&self.url)
+    }
+}
+
+pub struct Client {
+    inner: HttpClient<RequestError>,
+}
+
+impl Client {
+    pub fn build<D, O>(domain: D, oauth_token: O) -> Result<Self, Error>
+    where
+        D: AsRef<str>,
+        O: AsRef<str>,
+    {
+        let mut inner = HttpClient::build(
+            &format!("https://{}/api/v4", domain.as_ref()),
+            "application/json",
+        )?;
+        inner.set_authorization(Some(
+            header::HeaderValue::from_str(&format!("Bearer {}", oauth_token.as_ref()))
+                .map_err(|err| Error::InvalidHeaderValue("authorization", err))?,
+        ));
+
+        Ok(Self { inner })
+    }
+
+    /// Lists the releases of a project, following every page of results.
+    ///
+    /// The entity tag only applies to the first page, so when it is not modified no further
+    /// pages are requested and `None` is returned.
+    pub fn releases<O, N>(
+        &self,
+        owner: O,
+        name: N,
+        etag: Option<&ETag>,
+    ) -> impl Future<Item = Option<Response<Vec<Release>>>, Error = Error>
+    where
+        O: AsRef<str>,
+        N: AsRef<str>,
+    {
+        self.inner.get_pages(
+            self.inner.url(
+                format!("/projects/{}/releases", project_id(owner, name)),
+                Some(format!("per_page={}", PER_PAGE)),
+            ),
+            etag,
+            None,
+        )
+    }
+
+    pub fn manifest<U, A>(
+        &self,
+        url: U,
+        asset_name: A,
+    ) -> impl Future<Item = Manifest, Error = Error>
+    where
+        U: Into<String>,
+        A: Into<String>,
+    {
+        self.inner
+            .get_body(url.into())
+            .and_then(|bytes| Manifest::from_bytes(asset_name, bytes).map_err(Error::from))
+    }
+
+    /// Requests the bytes of an asset, passing on a `Range` header if given.
+    pub fn download<U: Into<String>>(
+        &self,
+        url: U,
+        range: Option<&str>,
+    ) -> impl Future<Item = ReqwestResponse, Error = Error> {
+        self.inner.get_stream(url.into(), range)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RequestError {
+    pub message: String,
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "request error: {}", self.message)
+    }
+}
+
+impl error::Error for RequestError {}
+
+impl ErrorBody for RequestError {
+    fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Returns the URL-encoded project path which GitLab accepts in place of a numeric project id.
+fn project_id<O, N>(owner: O, name: N) -> String
+where
+    O: AsRef<str>,
+    N: AsRef<str>,
+{
+    format!("{}%2F{}", owner.as_ref(), name.as_ref())
+}
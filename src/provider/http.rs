@@ -0,0 +1,692 @@
+use super::manifest::ParseError;
+use super::proxy::is_passthrough_status;
+use super::rate_limit::{self, RateLimit};
+use crate::ETag;
+use futures::{
+    future::{self, Either, Loop},
+    Future, Stream,
+};
+use log::{debug, error, warn};
+use reqwest::{
+    header,
+    r#async::{
+        Chunk, Client as ReqwestClient, Decoder, RequestBuilder, Response as ReqwestResponse,
+    },
+    StatusCode, Url,
+};
+use serde::de::DeserializeOwned;
+use std::error;
+use std::fmt;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+/// How long requests are held back after being refused for a rate limit which reported no time
+/// to retry after, as GitHub recommends for secondary rate limits.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+const NEXT_PAGE: &str = "x-next-page";
+
+/// The body of an API's error responses.
+pub trait ErrorBody: DeserializeOwned + fmt::Debug + fmt::Display + Send + Sync + 'static {
+    fn message(&self) -> &str;
+}
+
+#[derive(Debug)]
+pub struct Response<T> {
+    etag: Option<ETag>,
+    next: Option<String>,
+    payload: T,
+}
+
+impl<T> AsRef<T> for Response<T> {
+    fn as_ref(&self) -> &T {
+        &self.payload
+    }
+}
+
+impl<T> Response<T> {
+    /// Returns a response with a payload which did not come from a single page of the API.
+    pub(crate) fn new(etag: Option<ETag>, payload: T) -> Self {
+        Response {
+            etag,
+            next: None,
+            payload,
+        }
+    }
+
+    pub fn into_parts(self) -> (Option<ETag>, T) {
+        (self.etag, self.payload)
+    }
+
+    pub fn map<U, F>(self, f: F) -> Response<U>
+    where
+        F: FnOnce(T) -> U,
+    {
+        Response {
+            etag: self.etag,
+            next: self.next,
+            payload: f(self.payload),
+        }
+    }
+}
+
+/// A client of a provider's REST API, whose error responses have a body of type `E`.
+///
+/// Requests are only authorized when they are made to the API's origin, so that credentials are
+/// never sent to the other hosts which release assets can link to. When the client tracks a rate
+/// limit, requests fail fast while the limit is exhausted.
+pub struct HttpClient<E> {
+    inner: ReqwestClient,
+    base_url: Url,
+    authorization: Option<header::HeaderValue>,
+    rate_limit: Option<RateLimit>,
+    error_body: PhantomData<fn() -> E>,
+}
+
+impl<E> Clone for HttpClient<E> {
+    fn clone(&self) -> Self {
+        HttpClient {
+            inner: self.inner.clone(),
+            base_url: self.base_url.clone(),
+            authorization: self.authorization.clone(),
+            rate_limit: self.rate_limit.clone(),
+            error_body: PhantomData,
+        }
+    }
+}
+
+impl<E: ErrorBody> HttpClient<E> {
+    /// Builds a client of the API under `base_url`, such as `https://gitea.com/api/v1`, which
+    /// accepts responses of the `accept` media type.
+    pub fn build(base_url: &str, accept: &'static str) -> Result<Self, Error<E>> {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(header::ACCEPT, header::HeaderValue::from_static(accept));
+
+        Ok(HttpClient {
+            inner: ReqwestClient::builder()
+                .default_headers(headers)
+                .build()
+                .map_err(Error::Builder)?,
+            base_url: Url::parse(base_url).map_err(Error::InvalidUrl)?,
+            authorization: None,
+            rate_limit: None,
+            error_body: PhantomData,
+        })
+    }
+
+    /// Sets the value of the `Authorization` header which requests to the API are sent with.
+    pub fn set_authorization(&mut self, authorization: Option<header::HeaderValue>) {
+        self.authorization = authorization;
+    }
+
+    /// Returns a copy of the client which authorizes requests with another header value, for
+    /// APIs whose credentials are replaced as they expire.
+    pub fn with_authorization(&self, authorization: Option<header::HeaderValue>) -> Self {
+        let mut client = self.clone();
+        client.set_authorization(authorization);
+
+        client
+    }
+
+    /// Tracks the budget of the API's rate limit from every response.
+    pub fn set_rate_limit(&mut self, rate_limit: Option<RateLimit>) {
+        self.rate_limit = rate_limit;
+    }
+
+    pub fn rate_limit(&self) -> Option<&RateLimit> {
+        self.rate_limit.as_ref()
+    }
+
+    /// Returns the URL of a path under the API, with an optional query string.
+    pub fn url<P, Q>(&self, path: P, query: Option<Q>) -> String
+    where
+        P: AsRef<str>,
+        Q: AsRef<str>,
+    {
+        let mut url = format!(
+            "{}{}",
+            self.base_url.as_str().trim_end_matches('/'),
+            path.as_ref()
+        );
+        if let Some(query) = query {
+            url.push('?');
+            url.push_str(query.as_ref());
+        }
+
+        url
+    }
+
+    /// Requests a resource, returning `None` when it was not modified since its entity tag.
+    pub fn get<T: DeserializeOwned>(
+        &self,
+        url: String,
+        etag: Option<&ETag>,
+    ) -> impl Future<Item = Option<Response<T>>, Error = Error<E>> {
+        let mut req = self.inner.get(&url);
+        if let Some(etag) = etag {
+            let val = match header::HeaderValue::from_str(etag.as_ref()) {
+                Ok(val) => val,
+                Err(err) => return Either::A(future::err(Error::InvalidHeaderValue("etag", err))),
+            };
+            req = req.header(header::IF_NONE_MATCH, val);
+        }
+
+        let rate_limit = self.rate_limit.clone();
+        Either::B(self.send(&url, req).and_then(move |mut response| {
+            if response.status() == StatusCode::NOT_MODIFIED {
+                Either::A(future::ok(None))
+            } else if response.status().is_success() {
+                let etag = response_etag(&response);
+                let next = response_next_page(&response);
+
+                Either::B(Either::A(response.json().map_err(Error::Deserialize).map(
+                    |t| {
+                        Some(Response {
+                            etag,
+                            next,
+                            payload: t,
+                        })
+                    },
+                )))
+            } else if response.status() == StatusCode::NOT_FOUND {
+                Either::B(Either::B(Either::A(future::err(Error::NotFound))))
+            } else {
+                Either::B(Either::B(Either::B(error_response(response, rate_limit))))
+            }
+        }))
+    }
+
+    /// Requests a list, following every page of results up to `max` items.
+    ///
+    /// The entity tag only applies to the first page, so when it is not modified no further
+    /// pages are requested and `None` is returned.
+    pub fn get_pages<T>(
+        &self,
+        url: String,
+        etag: Option<&ETag>,
+        max: Option<usize>,
+    ) -> impl Future<Item = Option<Response<Vec<T>>>, Error = Error<E>>
+    where
+        T: DeserializeOwned + 'static,
+    {
+        let client = self.clone();
+
+        self.get::<Vec<T>>(url, etag)
+            .and_then(move |first| match first {
+                None => Either::A(future::ok(None)),
+                Some(first) => {
+                    Either::B(
+                        future::loop_fn(first, move |mut acc| {
+                            let next = match acc.next.take() {
+                                Some(next) => next,
+                                None => return Either::A(future::ok(Loop::Break(acc))),
+                            };
+                            if let Some(max) = max {
+                                if acc.payload.len() >= max {
+                                    return Either::A(future::ok(Loop::Break(acc)));
+                                }
+                            }
+
+                            Either::B(client.get::<Vec<T>>(next, None).map(
+                                move |page| match page {
+                                    Some(page) => {
+                                        acc.payload.extend(page.payload);
+                                        acc.next = page.next;
+                                        Loop::Continue(acc)
+                                    }
+                                    None => Loop::Break(acc),
+                                },
+                            ))
+                        })
+                        .map(Some),
+                    )
+                }
+            })
+            .map(move |response| {
+                response.map(|mut response| {
+                    if let Some(max) = max {
+                        response.payload.truncate(max);
+                    }
+                    response
+                })
+            })
+    }
+
+    /// Requests the bytes of a file, such as a release manifest.
+    pub fn get_body(&self, url: String) -> impl Future<Item = Chunk, Error = Error<E>> {
+        let req = self.inner.get(&url).header(
+            header::ACCEPT,
+            header::HeaderValue::from_static("application/octet-stream"),
+        );
+
+        let rate_limit = self.rate_limit.clone();
+        self.send(&url, req).and_then(move |mut response| {
+            if response.status().is_success() {
+                let body = std::mem::replace(response.body_mut(), Decoder::empty());
+                Either::A(body.concat2().map_err(Error::Response))
+            } else {
+                Either::B(error_response(response, rate_limit))
+            }
+        })
+    }
+
+    /// Requests the bytes of an asset, passing on a `Range` header if given.
+    pub fn get_stream(
+        &self,
+        url: String,
+        range: Option<&str>,
+    ) -> impl Future<Item = ReqwestResponse, Error = Error<E>> {
+        let mut req = self.inner.get(&url).header(
+            header::ACCEPT,
+            header::HeaderValue::from_static("application/octet-stream"),
+        );
+        if let Some(range) = range {
+            let val = match header::HeaderValue::from_str(range) {
+                Ok(val) => val,
+                Err(err) => return Either::A(future::err(Error::InvalidHeaderValue("range", err))),
+            };
+            req = req.header(header::RANGE, val);
+        }
+
+        let rate_limit = self.rate_limit.clone();
+        Either::B(self.send(&url, req).and_then(move |response| {
+            if is_passthrough_status(response.status()) {
+                Either::A(future::ok(response))
+            } else {
+                Either::B(error_response(response, rate_limit))
+            }
+        }))
+    }
+
+    /// Posts to an endpoint of the API without a body, authorized with `authorization` rather
+    /// than the client's own credentials.
+    pub fn post<T: DeserializeOwned>(
+        &self,
+        url: String,
+        authorization: header::HeaderValue,
+    ) -> impl Future<Item = T, Error = Error<E>> {
+        let req = self.inner.post(&url);
+        let rate_limit = self.rate_limit.clone();
+
+        self.with_authorization(Some(authorization))
+            .send(&url, req)
+            .and_then(move |mut response| {
+                if response.status().is_success() {
+                    Either::A(response.json().map_err(Error::Deserialize))
+                } else {
+                    Either::B(error_response(response, rate_limit))
+                }
+            })
+    }
+
+    /// Sends a request, authorizing it if it is to the API's origin.
+    fn send(
+        &self,
+        url: &str,
+        req: RequestBuilder,
+    ) -> impl Future<Item = ReqwestResponse, Error = Error<E>> {
+        if let Some(until) = self
+            .rate_limit
+            .as_ref()
+            .and_then(|rate_limit| rate_limit.budget().blocked_until())
+        {
+            return Either::A(future::err(Error::RateLimited(until)));
+        }
+        let req = match (Url::parse(url), &self.authorization) {
+            (Ok(ref url), Some(authorization)) if url.origin() == self.base_url.origin() => {
+                req.header(header::AUTHORIZATION, authorization.clone())
+            }
+            _ => req,
+        };
+
+        let rate_limit = self.rate_limit.clone();
+        Either::B(req.send().map_err(Error::Request).map(move |response| {
+            debug!("response: {:?}", response);
+            if let Some(rate_limit) = rate_limit {
+                rate_limit.update(response.headers());
+            }
+
+            response
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub enum Error<E> {
+    Api(E),
+    Builder(reqwest::Error),
+    Deserialize(reqwest::Error),
+    InvalidHeaderValue(&'static str, reqwest::header::InvalidHeaderValue),
+    InvalidUrl(reqwest::UrlError),
+    Manifest(ParseError),
+    MissingResponseField(&'static str),
+    NotFound,
+    /// A request was refused, or held back, for the API's rate limit until the given time in
+    /// seconds since the Unix epoch.
+    RateLimited(u64),
+    Request(reqwest::Error),
+    Response(reqwest::Error),
+}
+
+impl<E: ErrorBody> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Api(ref err) => fmt::Display::fmt(err, f),
+            Error::Builder(ref err) => err.fmt(f),
+            Error::Deserialize(ref err) => err.fmt(f),
+            Error::InvalidHeaderValue(ref name, ref err) => {
+                write!(f, "invalid header value for {}: {}", name, err)
+            }
+            Error::InvalidUrl(ref err) => write!(f, "invalid api url: {}", err),
+            Error::Manifest(ref err) => err.fmt(f),
+            Error::MissingResponseField(ref name) => write!(f, "missing response field: {}", name),
+            Error::NotFound => f.write_str("resource not found"),
+            Error::RateLimited(until) => write!(
+                f,
+                "rate limit exceeded until {}",
+                rate_limit::timestamp(*until).to_rfc3339()
+            ),
+            Error::Request(ref err) => err.fmt(f),
+            Error::Response(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl<E: ErrorBody> error::Error for Error<E> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Api(_) => None,
+            Error::Builder(ref err) => err.source(),
+            Error::Deserialize(ref err) => err.source(),
+            Error::InvalidHeaderValue(_, ref err) => err.source(),
+            Error::InvalidUrl(ref err) => err.source(),
+            Error::Manifest(ref err) => err.source(),
+            Error::MissingResponseField(_) => None,
+            Error::NotFound => None,
+            Error::RateLimited(_) => None,
+            Error::Request(ref err) => err.source(),
+            Error::Response(ref err) => err.source(),
+        }
+    }
+}
+
+impl<E> From<ParseError> for Error<E> {
+    fn from(err: ParseError) -> Self {
+        Error::Manifest(err)
+    }
+}
+
+/// Returns a future which fails with the error of an unsuccessful response.
+///
+/// When the client tracks a rate limit, responses which refuse a request for it, whether for a
+/// primary or secondary limit, fail with `Error::RateLimited` and hold back further requests
+/// until the limit allows them.
+fn error_response<T, E: ErrorBody>(
+    mut response: ReqwestResponse,
+    rate_limit: Option<RateLimit>,
+) -> impl Future<Item = T, Error = Error<E>> {
+    let status = response.status();
+    // The budget is checked before the body is read, as it was updated from this response
+    let blocked = rate_limit
+        .as_ref()
+        .is_some_and(|rate_limit| rate_limit.budget().blocked_until().is_some());
+
+    response
+        .json::<E>()
+        .map_err(Error::Deserialize)
+        .then(move |result| {
+            let rate_limit = match rate_limit {
+                Some(ref rate_limit) if is_rate_limited(status, blocked, result.as_ref().ok()) => {
+                    rate_limit
+                }
+                _ => return Err(result.map_or_else(|err| err, Error::Api)),
+            };
+
+            let until = rate_limited_until(rate_limit);
+            warn!("rate limit exceeded; until={}", until);
+
+            Err(Error::RateLimited(until))
+        })
+}
+
+/// Returns whether an error response refused a request for the rate limit, given whether the
+/// budget was blocked by the response's headers and the body of the response, if it was read.
+///
+/// GitHub answers a request over its primary limit with `403 Forbidden` and an exhausted budget,
+/// and one over a secondary limit with `403 Forbidden` and a message which mentions it.
+fn is_rate_limited<E: ErrorBody>(status: StatusCode, blocked: bool, body: Option<&E>) -> bool {
+    match status {
+        StatusCode::TOO_MANY_REQUESTS => true,
+        StatusCode::FORBIDDEN => {
+            blocked || body.is_some_and(|body| body.message().to_lowercase().contains("rate limit"))
+        }
+        _ => false,
+    }
+}
+
+/// Returns when requests may be made again after one was refused for the rate limit, holding
+/// them back for `DEFAULT_RETRY_AFTER` when the budget does not say.
+fn rate_limited_until(rate_limit: &RateLimit) -> u64 {
+    match rate_limit.budget().blocked_until() {
+        Some(until) => until,
+        None => {
+            rate_limit.hold_back(DEFAULT_RETRY_AFTER);
+            rate_limit
+                .budget()
+                .blocked_until()
+                .expect("requests were just held back")
+        }
+    }
+}
+
+fn response_etag(response: &ReqwestResponse) -> Option<ETag> {
+    match response.headers().get(header::ETAG) {
+        Some(header) => match header.to_str() {
+            Ok(s) => match s.parse() {
+                Ok(etag) => Some(etag),
+                Err(err) => {
+                    error!("etag header could not be parsed; err={}", err);
+                    None
+                }
+            },
+            Err(err) => {
+                error!("etag header was not utf8 clean; err={}", err);
+                None
+            }
+        },
+        None => {
+            warn!("etag header not found in response and was expected");
+            None
+        }
+    }
+}
+
+/// Returns the URL of the next page of results from a response, if any.
+///
+/// The `Link` header is preferred, falling back to the `X-Next-Page` header which GitLab sends
+/// when it leaves out links, such as for very large result sets.
+fn response_next_page(response: &ReqwestResponse) -> Option<String> {
+    let headers = response.headers();
+    if let Some(link) = headers
+        .get(header::LINK)
+        .and_then(|link| link.to_str().ok())
+    {
+        return next_link(link);
+    }
+
+    let page = headers.get(NEXT_PAGE)?.to_str().ok()?.trim();
+    if page.is_empty() {
+        return None;
+    }
+    let mut next = response.url().clone();
+    let query = next
+        .query_pairs()
+        .filter(|(key, _)| key != "page")
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect::<Vec<_>>();
+    next.query_pairs_mut()
+        .clear()
+        .extend_pairs(query)
+        .append_pair("page", page);
+
+    Some(next.into_string())
+}
+
+/// Returns the URL with `rel="next"` in the value of a `Link` header, if any.
+fn next_link(link: &str) -> Option<String> {
+    link.split(',').find_map(|part| {
+        let mut params = part.split(';').map(str::trim);
+        let url = params.next()?;
+        let is_next = params.any(|param| {
+            param.starts_with("rel=")
+                && param[4..]
+                    .trim_matches('"')
+                    .split_whitespace()
+                    .any(|rel| rel.eq_ignore_ascii_case("next"))
+        });
+        if is_next {
+            Some(
+                url.trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string(),
+            )
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct TestError {
+        message: String,
+    }
+
+    impl fmt::Display for TestError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(&self.message)
+        }
+    }
+
+    impl ErrorBody for TestError {
+        fn message(&self) -> &str {
+            &self.message
+        }
+    }
+
+    fn body(message: &str) -> TestError {
+        TestError {
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn rate_limited_statuses() {
+        let none = None::<&TestError>;
+        assert!(is_rate_limited(StatusCode::TOO_MANY_REQUESTS, false, none));
+        assert!(is_rate_limited(StatusCode::FORBIDDEN, true, none));
+        assert!(!is_rate_limited(StatusCode::FORBIDDEN, false, none));
+        assert!(!is_rate_limited(StatusCode::NOT_FOUND, true, none));
+        assert!(!is_rate_limited(
+            StatusCode::UNAUTHORIZED,
+            false,
+            Some(&body("rate limit"))
+        ));
+    }
+
+    #[test]
+    fn rate_limited_messages() {
+        let secondary =
+            body("You have exceeded a secondary rate limit. Please wait a few minutes.");
+        let primary = body("API Rate Limit exceeded for installation ID 1.");
+        let other = body("Resource not accessible by integration");
+
+        assert!(is_rate_limited(
+            StatusCode::FORBIDDEN,
+            false,
+            Some(&secondary)
+        ));
+        assert!(is_rate_limited(
+            StatusCode::FORBIDDEN,
+            false,
+            Some(&primary)
+        ));
+        assert!(!is_rate_limited(StatusCode::FORBIDDEN, false, Some(&other)));
+    }
+
+    #[test]
+    fn rate_limited_until_holds_back() {
+        let rate_limit = RateLimit::new();
+        let before = rate_limit::unix_now();
+        let until = rate_limited_until(&rate_limit);
+
+        assert!(until >= before + DEFAULT_RETRY_AFTER.as_secs());
+        assert_eq!(rate_limit.budget().blocked_until(), Some(until));
+        // A later refusal keeps the existing hold rather than extending it
+        assert_eq!(rate_limited_until(&rate_limit), until);
+    }
+
+    #[test]
+    fn error_messages() {
+        let err = reqwest::header::HeaderValue::from_str("\n").unwrap_err();
+        assert!(Error::<TestError>::InvalidHeaderValue("authorization", err)
+            .to_string()
+            .starts_with("invalid header value for authorization: "));
+        assert_eq!(
+            Error::<TestError>::MissingResponseField("url").to_string(),
+            "missing response field: url"
+        );
+        assert_eq!(
+            Error::<TestError>::NotFound.to_string(),
+            "resource not found"
+        );
+    }
+
+    #[test]
+    fn next_link_github() {
+        let link = "<https://api.github.com/repositories/1/releases?page=2>; rel=\"next\", \
+                    <https://api.github.com/repositories/1/releases?page=5>; rel=\"last\"";
+
+        assert_eq!(
+            next_link(link),
+            Some("https://api.github.com/repositories/1/releases?page=2".to_string())
+        );
+    }
+
+    #[test]
+    fn next_link_not_first() {
+        let link =
+            "<https://gitlab.example.com/api/v4/projects/1/releases?page=1>; rel=\"first\", \
+                    <https://gitlab.example.com/api/v4/projects/1/releases?page=3>; rel=\"next\"";
+
+        assert_eq!(
+            next_link(link),
+            Some("https://gitlab.example.com/api/v4/projects/1/releases?page=3".to_string())
+        );
+    }
+
+    #[test]
+    fn next_link_relation_forms() {
+        assert_eq!(
+            next_link("<https://example.com/?page=2>; rel=next"),
+            Some("https://example.com/?page=2".to_string())
+        );
+        assert_eq!(
+            next_link("<https://example.com/?page=2>;rel=\"prev next\""),
+            Some("https://example.com/?page=2".to_string())
+        );
+    }
+
+    #[test]
+    fn next_link_last_page() {
+        let link = "<https://example.com/?page=1>; rel=\"first\", \
+                    <https://example.com/?page=4>; rel=\"prev\"";
+
+        assert_eq!(next_link(link), None);
+        assert_eq!(next_link(""), None);
+    }
+}
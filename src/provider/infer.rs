@@ -0,0 +1,331 @@
+use super::manifest::{self, Manifest, ManifestEntry};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+lazy_static! {
+    /// Matches a Rust target triple in a file name, delimited by the start or end of the name or by
+    /// one of `-`, `_` or `.`.
+    static ref TRIPLE_RE: Regex = Regex::new(
+        r"(?x)
+        (?:^|[-_.])
+        (?P<target>
+            (?:x86_64|i[3-6]86|aarch64|arm64|armv[5-7][a-z]*|arm|mips64el|mips64|mipsel|mips
+                |powerpc64le|powerpc64|powerpc|riscv64gc|s390x|sparc64|wasm32)
+            (?:-(?:unknown|pc|apple|sun|uwp))?
+            -(?:linux|darwin|windows|freebsd|netbsd|openbsd|dragonfly|illumos|solaris|android
+                |ios|wasi)
+            (?:-(?:gnueabihf|gnueabi|gnux32|gnu|musleabihf|musleabi|musl|msvc|androideabi))?
+        )
+        (?:$|[-_.])"
+    )
+    .expect("target triple regex is valid");
+
+    /// Matches a version which trails an asset name, such as the `-v1.2.3` of `tool-v1.2.3`.
+    static ref VERSION_SUFFIX_RE: Regex =
+        Regex::new(r"(?:^|[-_.])v?[0-9]+(?:\.[0-9]+)*(?:-[0-9A-Za-z.]+)?$")
+            .expect("version suffix regex is valid");
+}
+
+/// File name suffixes of release files which are never assets, such as checksums and signatures.
+const SKIPPED_SUFFIXES: &[&str] = &[
+    ".asc",
+    ".md5",
+    ".minisig",
+    ".pem",
+    ".sha1",
+    ".sha256",
+    ".sha256sum",
+    ".sha512",
+    ".sig",
+];
+
+/// File name suffixes of archives, along with their archive format.
+const ARCHIVE_SUFFIXES: &[(&str, &str)] = &[
+    (".tar.gz", "tar.gz"),
+    (".tgz", "tar.gz"),
+    (".tar.bz2", "tar.bz2"),
+    (".tbz2", "tar.bz2"),
+    (".tar.xz", "tar.xz"),
+    (".txz", "tar.xz"),
+    (".tar.zst", "tar.zst"),
+    (".tar", "tar"),
+    (".zip", "zip"),
+    (".gz", "gz"),
+    (".xz", "xz"),
+];
+
+/// How the targets of a release are found when it publishes no manifest.
+#[derive(Clone, Debug, Default)]
+pub enum TargetInference {
+    /// Releases without a manifest have no targets.
+    #[default]
+    Off,
+    /// Targets are the Rust target triples found in asset file names.
+    Triples,
+    /// Targets are matched in asset file names by patterns with a `target` capture group, and an
+    /// optional `name` capture group for the asset name.
+    Patterns(Vec<Regex>),
+}
+
+impl TargetInference {
+    /// Builds manifests for a release's files, with one manifest for every asset name found.
+    ///
+    /// Asset names are taken from the text before the target in the file name, without any
+    /// trailing version, falling back to `default_name` when nothing is left. When several files
+    /// map to the same asset of a target, the first in file name order is kept.
+    pub(crate) fn manifests<'a, I>(&self, file_names: I, default_name: &str) -> Vec<Manifest>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut file_names = file_names
+            .into_iter()
+            .filter(|file_name| {
+                !manifest::is_manifest(file_name)
+                    && !SKIPPED_SUFFIXES
+                        .iter()
+                        .any(|suffix| file_name.ends_with(suffix))
+            })
+            .collect::<Vec<_>>();
+        file_names.sort();
+
+        let mut seen = HashSet::new();
+        let mut manifests = BTreeMap::new();
+        for file_name in file_names {
+            let (name, target) = match self.infer(file_name) {
+                Some(inferred) => inferred,
+                None => continue,
+            };
+            let name = if name.is_empty() {
+                default_name.to_string()
+            } else {
+                name
+            };
+            if !seen.insert((name.clone(), target.clone())) {
+                continue;
+            }
+
+            manifests
+                .entry(name)
+                .or_insert_with(Vec::new)
+                .push(ManifestEntry {
+                    target,
+                    asset: file_name.to_string(),
+                    content_type: None,
+                    sha256: None,
+                    archive: archive_format(file_name).map(ToString::to_string),
+                    aliases: Vec::new(),
+                });
+        }
+
+        manifests
+            .into_iter()
+            .map(|(name, entries)| Manifest {
+                name,
+                entries,
+                checksums: HashMap::new(),
+            })
+            .collect()
+    }
+
+    /// Returns the asset name and target found in a file name, if any.
+    fn infer(&self, file_name: &str) -> Option<(String, String)> {
+        match self {
+            TargetInference::Off => None,
+            TargetInference::Triples => TRIPLE_RE.captures(file_name).map(|caps| {
+                let target = caps.name("target").expect("target is always captured");
+                (
+                    asset_name(&file_name[..target.start()]),
+                    target.as_str().to_string(),
+                )
+            }),
+            TargetInference::Patterns(patterns) => patterns.iter().find_map(|pattern| {
+                let caps = pattern.captures(file_name)?;
+                let target = caps.name("target")?;
+                let name = match caps.name("name") {
+                    Some(name) => name.as_str().to_string(),
+                    None => asset_name(&file_name[..target.start()]),
+                };
+
+                Some((name, target.as_str().to_string()))
+            }),
+        }
+    }
+}
+
+impl fmt::Display for TargetInference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TargetInference::Off => f.write_str("off"),
+            TargetInference::Triples => f.write_str("triples"),
+            TargetInference::Patterns(patterns) => {
+                let patterns = patterns.iter().map(Regex::as_str).collect::<Vec<_>>();
+                write!(f, "patterns({})", patterns.join(", "))
+            }
+        }
+    }
+}
+
+/// Returns the asset name from the text which precedes a target in a file name.
+fn asset_name(prefix: &str) -> String {
+    let prefix = prefix.trim_end_matches(&['-', '_', '.'][..]);
+    VERSION_SUFFIX_RE.replace(prefix, "").into_owned()
+}
+
+fn archive_format(file_name: &str) -> Option<&'static str> {
+    ARCHIVE_SUFFIXES
+        .iter()
+        .find(|(suffix, _)| file_name.ends_with(suffix))
+        .map(|(_, format)| *format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triple(file_name: &str) -> Option<(String, String)> {
+        TargetInference::Triples.infer(file_name)
+    }
+
+    fn inferred(name: &str, target: &str) -> Option<(String, String)> {
+        Some((name.to_string(), target.to_string()))
+    }
+
+    #[test]
+    fn triples() {
+        assert_eq!(
+            triple("tool-x86_64-unknown-linux-gnu.tar.gz"),
+            inferred("tool", "x86_64-unknown-linux-gnu")
+        );
+        assert_eq!(
+            triple("tool-x86_64-unknown-linux-musl.tar.gz"),
+            inferred("tool", "x86_64-unknown-linux-musl")
+        );
+        assert_eq!(
+            triple("tool_aarch64-apple-darwin.zip"),
+            inferred("tool", "aarch64-apple-darwin")
+        );
+        assert_eq!(
+            triple("tool.x86_64-pc-windows-msvc.exe"),
+            inferred("tool", "x86_64-pc-windows-msvc")
+        );
+        assert_eq!(
+            triple("tool-armv7-unknown-linux-gnueabihf"),
+            inferred("tool", "armv7-unknown-linux-gnueabihf")
+        );
+        assert_eq!(
+            triple("x86_64-unknown-linux-gnu.tar.gz"),
+            inferred("", "x86_64-unknown-linux-gnu")
+        );
+    }
+
+    #[test]
+    fn triples_delimited() {
+        assert_eq!(triple("tool.tar.gz"), None);
+        assert_eq!(triple("toolx86_64-unknown-linux-gnu.tar.gz"), None);
+        assert_eq!(triple("tool-x86_64.tar.gz"), None);
+    }
+
+    #[test]
+    fn version_suffixes() {
+        assert_eq!(
+            triple("tool-v1.2.3-x86_64-unknown-linux-gnu.tar.gz"),
+            inferred("tool", "x86_64-unknown-linux-gnu")
+        );
+        assert_eq!(
+            triple("tool-1.2.3-rc.1-x86_64-apple-darwin.tar.gz"),
+            inferred("tool", "x86_64-apple-darwin")
+        );
+        assert_eq!(
+            triple("tool_2.0_x86_64-apple-darwin.tar.gz"),
+            inferred("tool", "x86_64-apple-darwin")
+        );
+        assert_eq!(
+            triple("v1.2.3-x86_64-apple-darwin.tar.gz"),
+            inferred("", "x86_64-apple-darwin")
+        );
+        assert_eq!(asset_name("tool2-"), "tool2");
+        assert_eq!(asset_name("tool-cli-v3"), "tool-cli");
+    }
+
+    #[test]
+    fn patterns() {
+        let inference = TargetInference::Patterns(vec![
+            Regex::new(r"^(?P<name>[a-z]+)_(?P<target>linux|macos)\.").unwrap(),
+            Regex::new(r"-(?P<target>win64)\.zip$").unwrap(),
+        ]);
+
+        assert_eq!(
+            inference.infer("tool_linux.tar.gz"),
+            inferred("tool", "linux")
+        );
+        assert_eq!(
+            inference.infer("tool-v1.0-win64.zip"),
+            inferred("tool", "win64")
+        );
+        assert_eq!(inference.infer("tool_freebsd.tar.gz"), None);
+        assert_eq!(TargetInference::Off.infer("tool_linux.tar.gz"), None);
+    }
+
+    #[test]
+    fn manifests_per_name() {
+        let file_names = vec![
+            "tool-v1.0-x86_64-unknown-linux-gnu.tar.gz",
+            "tool-v1.0-x86_64-unknown-linux-gnu.tar.gz.sha256",
+            "tool-v1.0-x86_64-unknown-linux-gnu.zip",
+            "tool-v1.0-x86_64-apple-darwin.zip",
+            "helper-x86_64-apple-darwin",
+            "x86_64-pc-windows-msvc.exe",
+            "tool.manifest.txt",
+            "README.md",
+        ];
+        let manifests = TargetInference::Triples.manifests(file_names, "repo");
+        let summary = manifests
+            .iter()
+            .map(|manifest| {
+                let entries = manifest
+                    .entries
+                    .iter()
+                    .map(|entry| {
+                        (
+                            entry.target.as_str(),
+                            entry.asset.as_str(),
+                            entry.archive.as_deref(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                (manifest.name.as_str(), entries)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "helper",
+                    vec![("x86_64-apple-darwin", "helper-x86_64-apple-darwin", None)]
+                ),
+                (
+                    "repo",
+                    vec![("x86_64-pc-windows-msvc", "x86_64-pc-windows-msvc.exe", None)]
+                ),
+                (
+                    "tool",
+                    vec![
+                        (
+                            "x86_64-apple-darwin",
+                            "tool-v1.0-x86_64-apple-darwin.zip",
+                            Some("zip")
+                        ),
+                        (
+                            "x86_64-unknown-linux-gnu",
+                            "tool-v1.0-x86_64-unknown-linux-gnu.tar.gz",
+                            Some("tar.gz")
+                        ),
+                    ]
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,755 @@
+use super::Error;
+use crate::{Asset, Release, Target};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::error;
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::Path;
+use std::str::{self, FromStr};
+
+/// The extension of manifests in the legacy format, with one whitespace separated entry per line.
+pub const MANIFEST_EXT: &str = ".manifest.txt";
+
+/// The extension of structured manifests in JSON.
+pub const MANIFEST_JSON_EXT: &str = ".manifest.json";
+
+/// The extension of structured manifests in TOML.
+pub const MANIFEST_TOML_EXT: &str = ".manifest.toml";
+
+/// The name of a release file listing the SHA-256 digests of its assets, as written by
+/// `sha256sum`.
+pub const CHECKSUMS_FILE: &str = "SHA256SUMS";
+
+/// The extensions of files next to an asset which hold its SHA-256 digest.
+const CHECKSUM_EXTS: &[&str] = &[".sha256", ".sha256sum"];
+
+/// The version of the structured manifest format which is understood.
+const MANIFEST_VERSION: u32 = 1;
+
+/// Archive formats which an asset can be declared as in a structured manifest.
+const ARCHIVE_FORMATS: &[&str] = &[
+    "tar", "tar.gz", "tar.bz2", "tar.xz", "tar.zst", "zip", "gz", "xz",
+];
+
+/// The format of a manifest, as given by the extension of its file name.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ManifestFormat {
+    Text,
+    Json,
+    Toml,
+    /// A list of asset digests in the format written by `sha256sum`, which gives no targets.
+    Checksums,
+}
+
+impl ManifestFormat {
+    /// Returns the format of a manifest file along with its name without the extension, or `None`
+    /// when the file is not a manifest.
+    ///
+    /// The name of a `SHA256SUMS` file is empty, and that of a checksum file next to an asset is
+    /// the file name of the asset.
+    pub fn detect(file_name: &str) -> Option<(Self, &str)> {
+        if file_name == CHECKSUMS_FILE {
+            return Some((ManifestFormat::Checksums, ""));
+        }
+        if let Some(ext) = CHECKSUM_EXTS.iter().find(|ext| file_name.ends_with(*ext)) {
+            return Some((
+                ManifestFormat::Checksums,
+                &file_name[..file_name.len() - ext.len()],
+            ));
+        }
+
+        [
+            (ManifestFormat::Text, MANIFEST_EXT),
+            (ManifestFormat::Json, MANIFEST_JSON_EXT),
+            (ManifestFormat::Toml, MANIFEST_TOML_EXT),
+        ]
+        .iter()
+        .find(|(_, ext)| file_name.ends_with(ext))
+        .map(|(format, ext)| (*format, &file_name[..file_name.len() - ext.len()]))
+    }
+}
+
+/// Returns whether a release file is a manifest, in any of the supported formats.
+pub fn is_manifest(file_name: &str) -> bool {
+    ManifestFormat::detect(file_name).is_some()
+}
+
+/// Returns the files of a release which are fetched as manifests.
+///
+/// Checksum files next to each asset are only fetched when the release has no `SHA256SUMS`, as
+/// fetching one file per asset would use up a provider's rate limit far quicker. For the same
+/// reason, providers drop those whose digest is already indexed with `reuse_checksums`.
+pub fn manifest_files<'a, I>(file_names: I) -> HashSet<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let manifests = file_names
+        .into_iter()
+        .filter_map(|file_name| {
+            ManifestFormat::detect(file_name).map(|(format, _)| (file_name, format))
+        })
+        .collect::<Vec<_>>();
+    let has_checksums_file = manifests
+        .iter()
+        .any(|(file_name, _)| *file_name == CHECKSUMS_FILE);
+
+    manifests
+        .into_iter()
+        .filter(|(file_name, format)| {
+            *format != ManifestFormat::Checksums
+                || !has_checksums_file
+                || *file_name == CHECKSUMS_FILE
+        })
+        .map(|(file_name, _)| file_name)
+        .collect()
+}
+
+/// Drops from the manifest files of a release the checksum files next to assets whose digest the
+/// index already holds, returning a manifest which lists those digests instead.
+///
+/// A digest is only reused when `is_unchanged` confirms that the indexed asset is still the file
+/// with the given name at the same revision, so that a change to the releases does not fetch the
+/// checksum file of every asset again.
+pub fn reuse_checksums<F>(
+    files: &mut HashSet<&str>,
+    indexed: Option<&Release>,
+    is_unchanged: F,
+) -> Manifest
+where
+    F: Fn(&str, &Asset) -> bool,
+{
+    let mut checksums = HashMap::new();
+    if let Some(indexed) = indexed {
+        files.retain(|file_name| {
+            let asset_name = match ManifestFormat::detect(file_name) {
+                Some((ManifestFormat::Checksums, name)) if !name.is_empty() => name,
+                _ => return true,
+            };
+            let sha256 = indexed
+                .targets()
+                .flat_map(Target::assets)
+                .filter(|asset| is_unchanged(asset_name, asset))
+                .find_map(Asset::sha256);
+
+            match sha256 {
+                Some(sha256) => {
+                    checksums.insert(asset_name.to_string(), sha256.to_string());
+                    false
+                }
+                None => true,
+            }
+        });
+    }
+
+    Manifest {
+        name: String::new(),
+        entries: Vec::new(),
+        checksums,
+    }
+}
+
+/// Returns whether any of the manifests of a release maps a target, so that targets need not be
+/// inferred.
+pub fn has_targets(manifests: &[Manifest]) -> bool {
+    manifests
+        .iter()
+        .any(|manifest| !manifest.entries.is_empty())
+}
+
+#[derive(Debug)]
+pub struct Manifest {
+    pub name: String,
+    pub entries: Vec<ManifestEntry>,
+    /// The SHA-256 digests of asset files, keyed by file name, as listed by checksum files.
+    pub checksums: HashMap<String, String>,
+}
+
+impl Manifest {
+    /// Parses the manifest file with the given name, in the format given by its extension.
+    ///
+    /// The name of the manifest is the file name without the extension.
+    pub fn from_bytes<N, I>(file_name: N, input: I) -> Result<Self, ParseError>
+    where
+        N: Into<String>,
+        I: AsRef<[u8]>,
+    {
+        let file_name = file_name.into();
+        let (format, name) = ManifestFormat::detect(&file_name)
+            .ok_or_else(|| ParseError::UnknownFormat(file_name.clone()))?;
+        let input = input.as_ref();
+
+        if format == ManifestFormat::Checksums {
+            return Ok(Manifest {
+                name: name.to_string(),
+                entries: Vec::new(),
+                checksums: parse_checksums(input, name)?,
+            });
+        }
+
+        let entries = match format {
+            ManifestFormat::Text => parse_text(input)?,
+            ManifestFormat::Json => {
+                let version = serde_json::from_slice::<StructuredVersion>(input)?.version;
+                check_version(version)?;
+                serde_json::from_slice::<StructuredManifest>(input)?.into_entries()?
+            }
+            ManifestFormat::Toml => {
+                let version = toml::from_slice::<StructuredVersion>(input)?.version;
+                check_version(version)?;
+                toml::from_slice::<StructuredManifest>(input)?.into_entries()?
+            }
+            ManifestFormat::Checksums => unreachable!("checksums are parsed above"),
+        };
+
+        Ok(Manifest {
+            name: name.to_string(),
+            entries,
+            checksums: HashMap::new(),
+        })
+    }
+}
+
+/// Parses a manifest in the legacy format, skipping blank lines and `#` comments.
+fn parse_text(input: &[u8]) -> Result<Vec<ManifestEntry>, ParseError> {
+    let mut entries = Vec::new();
+    for line in str::from_utf8(input)?.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        entries.push(line.parse()?);
+    }
+
+    Ok(entries)
+}
+
+/// Parses a checksum file in the format written by `sha256sum`, with a digest and a file name on
+/// each line. A line with only a digest is for `default_file`, as in a file next to an asset.
+///
+/// Lines which are not in this format are skipped rather than failing the release, as checksum
+/// files are not written for this server.
+fn parse_checksums(
+    input: &[u8],
+    default_file: &str,
+) -> Result<HashMap<String, String>, ParseError> {
+    let mut checksums = HashMap::new();
+    for line in str::from_utf8(input)?.lines() {
+        let mut fields = line.trim().splitn(2, char::is_whitespace);
+        let sha256 = match fields.next().map(parse_sha256) {
+            Some(Ok(sha256)) => sha256,
+            _ => continue,
+        };
+        // A leading `*` marks a file which was read in binary mode
+        let file_name = match fields
+            .next()
+            .map(|name| name.trim().trim_start_matches('*'))
+        {
+            Some(name) if !name.is_empty() => name,
+            _ if !default_file.is_empty() => default_file,
+            _ => continue,
+        };
+
+        checksums.insert(file_name.to_string(), sha256);
+    }
+
+    Ok(checksums)
+}
+
+fn check_version(version: u32) -> Result<(), ParseError> {
+    if version == MANIFEST_VERSION {
+        Ok(())
+    } else {
+        Err(ParseError::UnsupportedVersion(version))
+    }
+}
+
+/// The version of a structured manifest, read on its own before the rest of the manifest so that
+/// a newer format is reported as such rather than as an invalid manifest.
+#[derive(Deserialize)]
+struct StructuredVersion {
+    version: u32,
+}
+
+/// The entries of a structured manifest, read once its version is known to be supported.
+#[derive(Deserialize)]
+struct StructuredManifest {
+    #[serde(default)]
+    entries: Vec<StructuredEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StructuredEntry {
+    target: String,
+    asset: String,
+    content_type: Option<String>,
+    sha256: Option<String>,
+    archive: Option<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+}
+
+impl StructuredManifest {
+    fn into_entries(self) -> Result<Vec<ManifestEntry>, ManifestEntryParseError> {
+        self.entries
+            .into_iter()
+            .map(|entry| {
+                let archive = entry
+                    .archive
+                    .map(|archive| {
+                        let archive = archive.to_ascii_lowercase();
+                        if ARCHIVE_FORMATS.contains(&archive.as_str()) {
+                            Ok(archive)
+                        } else {
+                            Err(ManifestEntryParseError("unsupported archive format"))
+                        }
+                    })
+                    .transpose()?;
+
+                Ok(ManifestEntry {
+                    target: entry.target,
+                    asset: entry.asset,
+                    content_type: entry.content_type,
+                    sha256: entry.sha256.map(|s| parse_sha256(&s)).transpose()?,
+                    archive,
+                    aliases: entry.aliases,
+                })
+            })
+            .collect()
+    }
+}
+
+/// An entry of a manifest, which maps a target to an asset file.
+///
+/// Entries in the legacy format can only give the SHA-256 digest of the file as a third column,
+/// while structured manifests can describe the asset in full.
+#[derive(Debug)]
+pub struct ManifestEntry {
+    pub target: String,
+    pub asset: String,
+    pub content_type: Option<String>,
+    pub sha256: Option<String>,
+    pub archive: Option<String>,
+    /// Other names which the target can be requested by.
+    pub aliases: Vec<String>,
+}
+
+impl FromStr for ManifestEntry {
+    type Err = ManifestEntryParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s.split_ascii_whitespace().collect::<Vec<_>>();
+        let num_fields = fields.len();
+
+        if num_fields == 1 {
+            Err(ManifestEntryParseError(
+                "missing whitespace delimiter between fields",
+            ))
+        } else if num_fields > 3 {
+            Err(ManifestEntryParseError("more than three fields"))
+        } else if num_fields != 2 && num_fields != 3 {
+            unreachable!("invalid number of fields");
+        } else {
+            Ok(ManifestEntry {
+                target: fields[0].to_string(),
+                asset: fields[1].to_string(),
+                content_type: None,
+                sha256: fields.get(2).map(|f| parse_sha256(f)).transpose()?,
+                archive: None,
+                aliases: Vec::new(),
+            })
+        }
+    }
+}
+
+/// Parses a hex encoded SHA-256 digest, which may be prefixed with `sha256:`, into lowercase hex.
+fn parse_sha256(s: &str) -> Result<String, ManifestEntryParseError> {
+    let hex = s.trim_start_matches("sha256:");
+    if hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Ok(hex.to_ascii_lowercase())
+    } else {
+        Err(ManifestEntryParseError(
+            "checksum is not a hex encoded sha256 digest",
+        ))
+    }
+}
+
+/// Builds the targets of a release from its manifests.
+///
+/// The `asset_for_entry` function resolves the asset file found in a manifest entry into an asset
+/// with the given name, which is specific to each provider. A content type given by the manifest
+/// takes precedence over the provider's, and assets without either are given one guessed from the
+/// extension of their file. Likewise, a digest given by an entry takes precedence over one listed
+/// by the release's checksum files.
+pub fn targets_from_manifests<F>(
+    manifests: Vec<Manifest>,
+    asset_for_entry: F,
+) -> Result<Vec<Target>, Error>
+where
+    F: Fn(String, &str) -> Result<Asset, Error>,
+{
+    let checksums = manifests
+        .iter()
+        .flat_map(|manifest| manifest.checksums.iter())
+        .map(|(file_name, sha256)| (file_name.clone(), sha256.clone()))
+        .collect::<HashMap<_, _>>();
+
+    let mut targets = HashMap::new();
+    for manifest in manifests {
+        for entry in manifest.entries {
+            let ManifestEntry {
+                target: entry_target,
+                asset: entry_asset,
+                content_type,
+                sha256,
+                archive,
+                aliases,
+            } = entry;
+
+            let target = targets
+                .entry(entry_target.clone())
+                .or_insert_with(|| Target::new(entry_target));
+            for alias in aliases {
+                target.push_alias(alias);
+            }
+
+            let mut asset = asset_for_entry(manifest.name.clone(), &entry_asset)?;
+            if content_type.is_some() {
+                asset.set_content_type(content_type);
+            } else if asset.content_type().is_none() {
+                asset.set_content_type(Some(guess_content_type(&entry_asset)));
+            }
+            if let Some(sha256) = sha256.or_else(|| checksums.get(&entry_asset).cloned()) {
+                asset.set_sha256(Some(sha256));
+            }
+            asset.set_archive(archive);
+            target.push_asset(asset);
+        }
+    }
+
+    Ok(targets.into_values().collect())
+}
+
+fn guess_content_type(file_name: &str) -> String {
+    let ext = Path::new(file_name)
+        .extension()
+        .and_then(OsStr::to_str)
+        .unwrap_or_default();
+
+    actix_files::file_extension_to_mime(ext).to_string()
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    Entry(ManifestEntryParseError),
+    Json(serde_json::Error),
+    Toml(toml::de::Error),
+    UnknownFormat(String),
+    UnsupportedVersion(u32),
+    Utf8(str::Utf8Error),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Entry(ref err) => err.fmt(f),
+            ParseError::Json(ref err) => err.fmt(f),
+            ParseError::Toml(ref err) => err.fmt(f),
+            ParseError::UnknownFormat(ref file_name) => {
+                write!(f, "unknown manifest format: {}", file_name)
+            }
+            ParseError::UnsupportedVersion(version) => {
+                write!(f, "unsupported manifest version: {}", version)
+            }
+            ParseError::Utf8(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ParseError::Entry(ref err) => err.source(),
+            ParseError::Json(ref err) => err.source(),
+            ParseError::Toml(ref err) => err.source(),
+            ParseError::UnknownFormat(_) | ParseError::UnsupportedVersion(_) => None,
+            ParseError::Utf8(ref err) => err.source(),
+        }
+    }
+}
+
+impl From<str::Utf8Error> for ParseError {
+    fn from(err: str::Utf8Error) -> Self {
+        ParseError::Utf8(err)
+    }
+}
+
+impl From<serde_json::Error> for ParseError {
+    fn from(err: serde_json::Error) -> Self {
+        ParseError::Json(err)
+    }
+}
+
+impl From<toml::de::Error> for ParseError {
+    fn from(err: toml::de::Error) -> Self {
+        ParseError::Toml(err)
+    }
+}
+
+impl From<ManifestEntryParseError> for ParseError {
+    fn from(err: ManifestEntryParseError) -> Self {
+        ParseError::Entry(err)
+    }
+}
+
+#[derive(Debug)]
+pub struct ManifestEntryParseError(&'static str);
+
+impl fmt::Display for ManifestEntryParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl error::Error for ManifestEntryParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::Uri;
+
+    const SHA256: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    fn entry(entries: &[ManifestEntry], target: &str) -> (String, Option<String>) {
+        let entry = entries
+            .iter()
+            .find(|entry| entry.target == target)
+            .unwrap_or_else(|| panic!("missing target {}", target));
+
+        (entry.asset.clone(), entry.sha256.clone())
+    }
+
+    #[test]
+    fn detect_formats() {
+        assert_eq!(
+            ManifestFormat::detect("tool.manifest.txt"),
+            Some((ManifestFormat::Text, "tool"))
+        );
+        assert_eq!(
+            ManifestFormat::detect("tool.manifest.json"),
+            Some((ManifestFormat::Json, "tool"))
+        );
+        assert_eq!(
+            ManifestFormat::detect("tool.manifest.toml"),
+            Some((ManifestFormat::Toml, "tool"))
+        );
+        assert_eq!(
+            ManifestFormat::detect("SHA256SUMS"),
+            Some((ManifestFormat::Checksums, ""))
+        );
+        assert_eq!(
+            ManifestFormat::detect("tool.tar.gz.sha256"),
+            Some((ManifestFormat::Checksums, "tool.tar.gz"))
+        );
+        assert_eq!(ManifestFormat::detect("tool.tar.gz"), None);
+        assert_eq!(ManifestFormat::detect("manifest.txt"), None);
+    }
+
+    #[test]
+    fn parse_text_entries() {
+        let input = format!(
+            "# targets of tool\n\
+             \n\
+             x86_64-unknown-linux-gnu  tool-linux.tar.gz  sha256:{}\n\
+             \tx86_64-apple-darwin tool-macos.tar.gz\n",
+            SHA256.to_ascii_uppercase()
+        );
+        let entries = parse_text(input.as_bytes()).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entry(&entries, "x86_64-unknown-linux-gnu"),
+            ("tool-linux.tar.gz".to_string(), Some(SHA256.to_string()))
+        );
+        assert_eq!(
+            entry(&entries, "x86_64-apple-darwin"),
+            ("tool-macos.tar.gz".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn parse_text_invalid() {
+        assert!(parse_text(b"x86_64-unknown-linux-gnu\n").is_err());
+        assert!(parse_text(b"a b c d\n").is_err());
+        assert!(parse_text(b"a b not-a-digest\n").is_err());
+    }
+
+    #[test]
+    fn json_manifest() {
+        let input = format!(
+            r#"{{
+                "version": 1,
+                "entries": [
+                    {{
+                        "target": "x86_64-unknown-linux-gnu",
+                        "asset": "tool-linux.tar.gz",
+                        "content_type": "application/gzip",
+                        "sha256": "{}",
+                        "archive": "TAR.GZ",
+                        "aliases": ["linux-amd64"]
+                    }}
+                ]
+            }}"#,
+            SHA256
+        );
+        let manifest = Manifest::from_bytes("tool.manifest.json", input).unwrap();
+
+        assert_eq!(manifest.name, "tool");
+        assert_eq!(manifest.entries.len(), 1);
+        let entry = &manifest.entries[0];
+        assert_eq!(entry.target, "x86_64-unknown-linux-gnu");
+        assert_eq!(entry.asset, "tool-linux.tar.gz");
+        assert_eq!(entry.content_type.as_ref().unwrap(), "application/gzip");
+        assert_eq!(entry.sha256.as_ref().unwrap(), SHA256);
+        assert_eq!(entry.archive.as_ref().unwrap(), "tar.gz");
+        assert_eq!(entry.aliases, vec!["linux-amd64".to_string()]);
+    }
+
+    #[test]
+    fn toml_manifest() {
+        let input = format!(
+            r#"
+            version = 1
+
+            [[entries]]
+            target = "x86_64-unknown-linux-gnu"
+            asset = "tool-linux.tar.gz"
+            sha256 = "sha256:{}"
+
+            [[entries]]
+            target = "x86_64-pc-windows-msvc"
+            asset = "tool-windows.zip"
+            archive = "zip"
+            "#,
+            SHA256
+        );
+        let manifest = Manifest::from_bytes("tool.manifest.toml", input).unwrap();
+
+        assert_eq!(manifest.name, "tool");
+        assert_eq!(manifest.entries.len(), 2);
+        assert_eq!(
+            entry(&manifest.entries, "x86_64-unknown-linux-gnu"),
+            ("tool-linux.tar.gz".to_string(), Some(SHA256.to_string()))
+        );
+        assert_eq!(
+            entry(&manifest.entries, "x86_64-pc-windows-msvc"),
+            ("tool-windows.zip".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn structured_manifest_invalid() {
+        match Manifest::from_bytes("tool.manifest.json", r#"{"version": 2, "entries": 1}"#) {
+            Err(ParseError::UnsupportedVersion(2)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(Manifest::from_bytes(
+            "tool.manifest.toml",
+            "version = 1\n[[entries]]\ntarget = \"a\"\nasset = \"b\"\nunknown = 1\n",
+        )
+        .is_err());
+        assert!(Manifest::from_bytes(
+            "tool.manifest.toml",
+            "version = 1\n[[entries]]\ntarget = \"a\"\nasset = \"b\"\narchive = \"rar\"\n",
+        )
+        .is_err());
+        match Manifest::from_bytes("tool.tar.gz", "") {
+            Err(ParseError::UnknownFormat(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn checksum_files() {
+        let input = format!(
+            "{sha}  tool-linux.tar.gz\n{sha} *tool-windows.zip\nnot a checksum line\n",
+            sha = SHA256
+        );
+        let manifest = Manifest::from_bytes(CHECKSUMS_FILE, input).unwrap();
+        assert!(manifest.entries.is_empty());
+        assert_eq!(manifest.checksums.len(), 2);
+        assert_eq!(manifest.checksums["tool-linux.tar.gz"], SHA256);
+        assert_eq!(manifest.checksums["tool-windows.zip"], SHA256);
+
+        let manifest = Manifest::from_bytes("tool-linux.tar.gz.sha256", SHA256).unwrap();
+        assert_eq!(manifest.checksums["tool-linux.tar.gz"], SHA256);
+    }
+
+    #[test]
+    fn manifest_files_prefer_checksums_file() {
+        let names = vec![
+            "tool.manifest.txt",
+            "tool-linux.tar.gz",
+            "tool-linux.tar.gz.sha256",
+        ];
+        let mut files = manifest_files(names.iter().cloned())
+            .into_iter()
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, vec!["tool-linux.tar.gz.sha256", "tool.manifest.txt"]);
+
+        let mut names = names;
+        names.push(CHECKSUMS_FILE);
+        let mut files = manifest_files(names).into_iter().collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, vec![CHECKSUMS_FILE, "tool.manifest.txt"]);
+    }
+
+    #[test]
+    fn reuse_checksums_of_unchanged_assets() {
+        let mut target = Target::new("x86_64-unknown-linux-gnu");
+        for file_name in &["tool-linux.tar.gz", "tool-windows.zip"] {
+            let uri = format!("https://example.com/{}", file_name);
+            let mut asset = Asset::new(*file_name, uri.parse::<Uri>().unwrap());
+            asset.set_revision(Some("r1"));
+            if *file_name == "tool-linux.tar.gz" {
+                asset.set_sha256(Some(SHA256));
+            }
+            target.push_asset(asset);
+        }
+        let mut indexed = Release::new(1, "v1.0");
+        indexed.set_targets(vec![target]);
+
+        let names = [
+            "tool.manifest.txt",
+            "tool-linux.tar.gz.sha256",
+            "tool-windows.zip.sha256",
+        ];
+        let reuse = |revision: &'static str, indexed: Option<&Release>| {
+            let mut files = manifest_files(names.iter().cloned());
+            let manifest = reuse_checksums(&mut files, indexed, |file_name, asset| {
+                asset.revision() == Some(revision)
+                    && asset
+                        .download_uri()
+                        .is_some_and(|uri| uri.path() == format!("/{}", file_name))
+            });
+            let mut files = files.into_iter().collect::<Vec<_>>();
+            files.sort();
+            (files, manifest.checksums)
+        };
+
+        let (files, checksums) = reuse("r1", Some(&indexed));
+        assert_eq!(files, vec!["tool-windows.zip.sha256", "tool.manifest.txt"]);
+        assert_eq!(checksums.len(), 1);
+        assert_eq!(checksums["tool-linux.tar.gz"], SHA256);
+
+        let (files, checksums) = reuse("r2", Some(&indexed));
+        assert_eq!(files.len(), 3);
+        assert!(checksums.is_empty());
+
+        let (files, checksums) = reuse("r1", None);
+        assert_eq!(files.len(), 3);
+        assert!(checksums.is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+use super::Error;
+use actix_web::web::Bytes;
+use futures::Stream;
+use reqwest::{
+    header::{self, HeaderMap, HeaderName},
+    r#async::{Decoder, Response},
+    StatusCode,
+};
+
+/// Response headers which are passed on from the remote to the client.
+const PASSTHROUGH_HEADERS: &[HeaderName] = &[
+    header::ACCEPT_RANGES,
+    header::CONTENT_RANGE,
+    header::CONTENT_TYPE,
+    header::ETAG,
+    header::LAST_MODIFIED,
+];
+
+/// The bytes of a remote asset, as fetched through a provider's client when proxying downloads.
+pub struct AssetStream {
+    status: StatusCode,
+    headers: HeaderMap,
+    content_length: Option<u64>,
+    body: Box<dyn Stream<Item = Bytes, Error = Error>>,
+}
+
+impl AssetStream {
+    pub fn new<S>(
+        status: StatusCode,
+        headers: HeaderMap,
+        content_length: Option<u64>,
+        body: S,
+    ) -> Self
+    where
+        S: Stream<Item = Bytes, Error = Error> + 'static,
+    {
+        AssetStream {
+            status,
+            headers,
+            content_length,
+            body: Box::new(body),
+        }
+    }
+
+    /// Builds an asset stream from a remote response, keeping only the headers which describe the
+    /// asset bytes.
+    pub(crate) fn from_response(mut response: Response) -> Self {
+        let mut headers = HeaderMap::new();
+        for name in PASSTHROUGH_HEADERS {
+            if let Some(value) = response.headers().get(name) {
+                headers.insert(name.clone(), value.clone());
+            }
+        }
+        let body = std::mem::replace(response.body_mut(), Decoder::empty())
+            .map(|chunk| Bytes::from(chunk.as_ref()))
+            .map_err(|err| Error::Client(Box::new(err)));
+
+        Self::new(response.status(), headers, response.content_length(), body)
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the headers to send along with the asset bytes, other than `Content-Length`.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+
+    pub fn into_body(self) -> Box<dyn Stream<Item = Bytes, Error = Error>> {
+        self.body
+    }
+}
+
+/// Returns whether a remote response to an asset download can be passed on to the client.
+pub(crate) fn is_passthrough_status(status: StatusCode) -> bool {
+    status.is_success() || status == StatusCode::RANGE_NOT_SATISFIABLE
+}
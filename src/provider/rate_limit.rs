@@ -0,0 +1,246 @@
+use chrono::{DateTime, TimeZone, Utc};
+use log::warn;
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const LIMIT: &str = "x-ratelimit-limit";
+const REMAINING: &str = "x-ratelimit-remaining";
+const RESET: &str = "x-ratelimit-reset";
+const RETRY_AFTER: &str = "retry-after";
+
+/// The fraction of its limit below which a budget is low.
+const LOW_FRACTION: u64 = 10;
+
+/// The request budget of an API's rate limit, as last reported by its responses.
+///
+/// Times are in seconds since the Unix epoch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Budget {
+    /// The number of requests allowed in each rate limit window.
+    pub limit: Option<u64>,
+    /// The number of requests left in the current window.
+    pub remaining: Option<u64>,
+    /// When the current window ends and the budget is refilled.
+    pub reset: Option<u64>,
+    /// When requests may be made again after the API asked for them to be held back, such as for
+    /// a secondary rate limit.
+    pub retry_after: Option<u64>,
+}
+
+impl Budget {
+    /// Returns when requests may be made again, if they must be held back until then.
+    pub fn blocked_until(&self) -> Option<u64> {
+        let now = unix_now();
+        let retry_after = self.retry_after.filter(|&at| at > now);
+        let reset = match self.remaining {
+            Some(0) => self.reset.filter(|&at| at > now),
+            _ => None,
+        };
+
+        retry_after.into_iter().chain(reset).max()
+    }
+
+    /// Returns whether less than a tenth of the limit remains.
+    pub fn is_low(&self) -> bool {
+        match (self.limit, self.remaining) {
+            (Some(limit), Some(remaining)) => remaining < limit / LOW_FRACTION,
+            _ => false,
+        }
+    }
+
+    /// Returns how long each of `repos` repos should wait between updates so that the budget
+    /// lasts until it is reset, if it is low or exhausted.
+    ///
+    /// Each update is counted as a single request, as most are answered from entity tags.
+    pub fn throttle(&self, repos: usize) -> Option<Duration> {
+        if let Some(until) = self.blocked_until() {
+            return Some(Duration::from_secs(until.saturating_sub(unix_now())));
+        }
+        if !self.is_low() {
+            return None;
+        }
+
+        let until_reset = self.reset?.checked_sub(unix_now())?;
+        let remaining = self.remaining?.max(1);
+
+        Some(Duration::from_secs(
+            until_reset.saturating_mul(repos as u64) / remaining,
+        ))
+    }
+}
+
+/// A rate limit budget which is shared by every request of a client.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimit(Arc<Mutex<Budget>>);
+
+impl RateLimit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the current budget.
+    pub fn budget(&self) -> Budget {
+        *self.0.lock().expect("lock poisoned")
+    }
+
+    /// Updates the budget from the rate limit headers of a response, keeping any values which the
+    /// response does not report.
+    pub fn update(&self, headers: &HeaderMap) {
+        let mut budget = self.0.lock().expect("lock poisoned");
+        if let Some(limit) = header_u64(headers, LIMIT) {
+            budget.limit = Some(limit);
+        }
+        if let Some(remaining) = header_u64(headers, REMAINING) {
+            budget.remaining = Some(remaining);
+        }
+        if let Some(reset) = header_u64(headers, RESET) {
+            budget.reset = Some(reset);
+        }
+        if let Some(secs) = header_u64(headers, RETRY_AFTER) {
+            budget.retry_after = Some(unix_now().saturating_add(secs));
+        }
+    }
+
+    /// Holds back requests for a duration, for when the API refused a request for its rate limit
+    /// without saying for how long.
+    pub fn hold_back(&self, duration: Duration) {
+        let mut budget = self.0.lock().expect("lock poisoned");
+        let until = unix_now().saturating_add(duration.as_secs());
+        budget.retry_after = Some(budget.retry_after.map_or(until, |at| at.max(until)));
+    }
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    let value = headers.get(name)?;
+    match value.to_str().ok().and_then(|s| s.trim().parse().ok()) {
+        Some(value) => Some(value),
+        None => {
+            warn!("rate limit header could not be parsed; header={}", name);
+            None
+        }
+    }
+}
+
+/// Returns the number of seconds since the Unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+}
+
+/// Converts a number of seconds since the Unix epoch to a timestamp, saturating at the latest
+/// representable one for times which are out of range, such as from a bogus header.
+pub fn timestamp(secs: u64) -> DateTime<Utc> {
+    i64::try_from(secs)
+        .ok()
+        .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+        .unwrap_or_else(|| chrono::MAX_DATE.and_hms(23, 59, 59))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderName, HeaderValue};
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(
+                HeaderName::from_static(name),
+                HeaderValue::from_str(value).unwrap(),
+            );
+        }
+
+        headers
+    }
+
+    fn budget(limit: u64, remaining: u64, reset: u64) -> Budget {
+        Budget {
+            limit: Some(limit),
+            remaining: Some(remaining),
+            reset: Some(reset),
+            retry_after: None,
+        }
+    }
+
+    #[test]
+    fn update_keeps_unreported_values() {
+        let rate_limit = RateLimit::new();
+        rate_limit.update(&headers(&[
+            (LIMIT, "5000"),
+            (REMAINING, "4999"),
+            (RESET, "1600000000"),
+        ]));
+        rate_limit.update(&headers(&[(REMAINING, "4998"), (LIMIT, "not a number")]));
+
+        assert_eq!(rate_limit.budget(), budget(5000, 4998, 1_600_000_000));
+    }
+
+    #[test]
+    fn update_retry_after() {
+        let rate_limit = RateLimit::new();
+        let before = unix_now();
+        rate_limit.update(&headers(&[(RETRY_AFTER, "30")]));
+
+        let until = rate_limit.budget().blocked_until().unwrap();
+        assert!(until >= before + 30 && until <= unix_now() + 30);
+    }
+
+    #[test]
+    fn exhausted_until_reset() {
+        let reset = unix_now() + 600;
+        let exhausted = budget(5000, 0, reset);
+
+        assert_eq!(exhausted.blocked_until(), Some(reset));
+        let throttle = exhausted.throttle(10).unwrap();
+        assert!(throttle <= Duration::from_secs(600) && throttle >= Duration::from_secs(598));
+    }
+
+    #[test]
+    fn reset_in_the_past() {
+        let reset = unix_now() - 10;
+
+        assert_eq!(budget(5000, 0, reset).blocked_until(), None);
+        assert_eq!(budget(5000, 0, reset).throttle(10), None);
+        assert_eq!(budget(5000, 10, reset).throttle(10), None);
+    }
+
+    #[test]
+    fn low_below_a_tenth() {
+        let reset = unix_now() + 1000;
+
+        assert!(!budget(5000, 500, reset).is_low());
+        assert_eq!(budget(5000, 500, reset).throttle(10), None);
+
+        let low = budget(5000, 499, reset);
+        assert!(low.is_low());
+        let throttle = low.throttle(499).unwrap();
+        assert!(throttle <= Duration::from_secs(1000) && throttle >= Duration::from_secs(998));
+
+        assert!(!Budget::default().is_low());
+        assert_eq!(Budget::default().throttle(10), None);
+    }
+
+    #[test]
+    fn overflow_saturates() {
+        let rate_limit = RateLimit::new();
+        rate_limit.update(&headers(&[(RETRY_AFTER, "18446744073709551615")]));
+        assert_eq!(rate_limit.budget().retry_after, Some(u64::MAX));
+
+        rate_limit.hold_back(Duration::from_secs(u64::MAX));
+        assert_eq!(rate_limit.budget().blocked_until(), Some(u64::MAX));
+
+        let low = budget(5000, 1, u64::MAX);
+        assert_eq!(
+            low.throttle(usize::MAX),
+            Some(Duration::from_secs(u64::MAX))
+        );
+
+        assert_eq!(timestamp(u64::MAX), chrono::MAX_DATE.and_hms(23, 59, 59));
+        assert_eq!(timestamp(0).timestamp(), 0);
+    }
+}
@@ -0,0 +1,76 @@
+use super::Error;
+use crate::Repo;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+/// The repos of one owner, keyed by name.
+type OwnerRepos = HashMap<String, RwLock<Arc<Repo>>>;
+
+/// A shareable map of repos, keyed by owner and then by name.
+#[derive(Clone)]
+pub(crate) struct RepoMap(Arc<HashMap<String, OwnerRepos>>);
+
+impl RepoMap {
+    pub(crate) fn new<R>(iter: R) -> Self
+    where
+        R: IntoIterator<Item = Repo>,
+    {
+        let mut repos = HashMap::new();
+        for repo in iter {
+            repos
+                .entry(repo.owner().to_string())
+                .or_insert_with(HashMap::new)
+                .insert(repo.name().to_string(), RwLock::new(Arc::new(repo)));
+        }
+
+        RepoMap(Arc::new(repos))
+    }
+
+    pub(crate) fn repos<'a>(&'a self) -> impl Iterator<Item = Arc<Repo>> + 'a {
+        self.0
+            .values()
+            .flat_map(|i| i.values().map(|r| r.read().expect("lock poisoned").clone()))
+    }
+
+    pub(crate) fn repo(&self, owner: &str, name: &str) -> Option<Arc<Repo>> {
+        self.0
+            .get(owner)
+            .and_then(|o| o.get(name))
+            .map(|r| r.read().expect("lock poisoned").clone())
+    }
+
+    pub(crate) fn repo_mut<F>(&self, owner: &str, name: &str, update: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Repo),
+    {
+        let mut repo = self
+            .0
+            .get(owner)
+            .and_then(|o| o.get(name))
+            .map(|r| r.write().expect("lock poisoned"))
+            .ok_or(Error::RepoNotFound)?;
+
+        update(Arc::make_mut(&mut repo));
+
+        Ok(())
+    }
+
+    /// Records that an update of a repo succeeded, whether or not its releases changed.
+    pub(crate) fn mark_updated(&self, owner: &str, name: &str) -> Result<(), Error> {
+        self.repo_mut(owner, name, |repo| repo.set_updated(Some(Instant::now())))
+    }
+
+    pub(crate) fn restore(&self, snapshot: Repo) -> Result<(), Error> {
+        let (owner, name) = (snapshot.owner().to_string(), snapshot.name().to_string());
+
+        self.repo_mut(&owner, &name, |repo| repo.restore_index(snapshot))
+    }
+}
+
+impl fmt::Debug for RepoMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
@@ -0,0 +1,278 @@
+use super::infer::TargetInference;
+use super::manifest;
+use super::proxy::AssetStream;
+use super::repo_map::RepoMap;
+use super::{parse_timestamp, release_id, Error, ReleaseSource, NO_ETAG};
+use crate::{metrics, Asset, Release, Repo};
+use actix_web::http::Uri;
+use futures::{
+    future::{self, Either},
+    Future,
+};
+use log::info;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub mod client;
+
+/// How long a presigned asset URL remains valid.
+const PRESIGN_EXPIRY: Duration = Duration::from_secs(60 * 60);
+
+/// A provider which indexes releases from an S3-compatible bucket.
+///
+/// Each repo is the `<owner>/<repo>/` prefix in the bucket, with one sub-prefix per version
+/// containing the release's `*.manifest.txt` objects alongside the assets they reference.
+pub struct S3 {
+    domain: String,
+    client: Arc<client::Client>,
+    repos: RepoMap,
+}
+
+impl fmt::Display for S3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "provider::s3({})", &self.domain)
+    }
+}
+
+impl fmt::Debug for S3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("S3")
+            .field("domain", &self.domain)
+            .field("repos", &self.repos)
+            .finish()
+    }
+}
+
+impl S3 {
+    pub fn build<S, R>(domain: S, client: client::Client, iter: R) -> Result<Self, Error>
+    where
+        S: Into<String>,
+        R: IntoIterator<Item = Repo>,
+    {
+        Ok(S3 {
+            domain: domain.into(),
+            client: Arc::new(client),
+            repos: RepoMap::new(iter),
+        })
+    }
+}
+
+impl ReleaseSource for S3 {
+    fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    fn repos<'a>(&'a self) -> Box<dyn Iterator<Item = Arc<Repo>> + 'a> {
+        Box::new(self.repos.repos())
+    }
+
+    fn repo(&self, owner: &str, name: &str) -> Option<Arc<Repo>> {
+        self.repos.repo(owner, name)
+    }
+
+    fn restore_repo(&self, snapshot: Repo) -> Result<(), Error> {
+        self.repos.restore(snapshot)
+    }
+
+    /// Returns a presigned URI for an asset's object, as bucket objects are generally private.
+    fn download_uri(&self, uri: &Uri) -> Result<Uri, Error> {
+        self.client
+            .presign(uri, PRESIGN_EXPIRY)
+            .map_err(|err| Error::Client(Box::new(err)))
+    }
+
+    fn fetch_asset(
+        &self,
+        asset: &Asset,
+        range: Option<String>,
+    ) -> Box<dyn Future<Item = AssetStream, Error = Error>> {
+        let uri = match asset.proxy_uri() {
+            Some(uri) => uri,
+            None => return Box::new(future::err(Error::ProxyUnsupported)),
+        };
+
+        Box::new(
+            self.client
+                .download(uri, range.as_deref())
+                .map(AssetStream::from_response)
+                .map_err(|err| Error::Client(Box::new(err))),
+        )
+    }
+
+    fn update_repo(&self, owner: &str, name: &str) -> Box<dyn Future<Item = (), Error = Error>> {
+        let owner: Arc<str> = owner.into();
+        let name: Arc<str> = name.into();
+        let domain: Arc<str> = self.domain.clone().into();
+
+        let repo = match self.repo(&owner, &name) {
+            Some(repo) => repo,
+            None => return Box::new(future::err(Error::RepoNotFound)),
+        };
+        let etag = repo.releases_etag().cloned();
+        let inference = repo.target_inference().clone();
+
+        let client = self.client.clone();
+        let repos = self.repos.clone();
+        let prefix = format!("{}/{}/", owner, name);
+
+        Box::new(
+            client
+                .list_objects(prefix.clone())
+                .map_err(|err| Error::Client(Box::new(err)))
+                .and_then(move |objects| {
+                    let next_etag = client::listing_etag(&objects);
+                    if etag.is_some() {
+                        metrics::global().etag_response(
+                            &domain,
+                            &owner,
+                            &name,
+                            "releases",
+                            etag.as_ref() != Some(&next_etag),
+                        );
+                    }
+                    if etag.as_ref() == Some(&next_etag) {
+                        info!(
+                            "releases not modified; domain={}, repo={}/{}, etag={}",
+                            &domain,
+                            &owner,
+                            &name,
+                            etag.as_ref().map(|e| e.as_ref()).unwrap_or_else(|| NO_ETAG)
+                        );
+
+                        return Either::A(future::result(repos.mark_updated(&owner, &name)));
+                    }
+
+                    Either::B(
+                        process_objects(client, &prefix, objects, &repo, name.clone(), inference)
+                            .and_then(move |(releases, latest)| {
+                                repos.repo_mut(&owner, &name, |repo| {
+                                    repo.set_releases_etag(Some(next_etag.clone()));
+                                    repo.set_releases(releases);
+                                    repo.set_latest_prerelease(latest.clone());
+                                    repo.set_latest_release(latest);
+                                    repo.set_updated(Some(Instant::now()));
+                                })?;
+                                info!(
+                                    "releases updated; domain={}, repo={}/{}, next_etag={}",
+                                    &domain, &owner, &name, next_etag
+                                );
+
+                                Ok(())
+                            }),
+                    )
+                }),
+        )
+    }
+}
+
+/// The objects under a version prefix, keyed by their name relative to that prefix.
+#[derive(Default)]
+struct VersionObjects {
+    objects: HashMap<String, client::Object>,
+    last_modified: String,
+}
+
+fn process_objects(
+    client: Arc<client::Client>,
+    prefix: &str,
+    objects: Vec<client::Object>,
+    indexed: &Repo,
+    repo_name: Arc<str>,
+    inference: TargetInference,
+) -> impl Future<Item = (Vec<Release>, Option<String>), Error = Error> {
+    let mut versions = BTreeMap::<String, VersionObjects>::new();
+    for object in objects {
+        let (version, file_name) = {
+            let mut parts = object.key[prefix.len()..].splitn(2, '/');
+            match (parts.next(), parts.next()) {
+                (Some(version), Some(file_name))
+                    if !version.is_empty() && !file_name.is_empty() && !file_name.contains('/') =>
+                {
+                    (version.to_string(), file_name.to_string())
+                }
+                _ => continue,
+            }
+        };
+
+        let entry = versions.entry(version).or_default();
+        if object.last_modified > entry.last_modified {
+            entry.last_modified = object.last_modified.clone();
+        }
+        entry.objects.insert(file_name, object);
+    }
+
+    let latest = versions
+        .iter()
+        .max_by(|a, b| a.1.last_modified.cmp(&b.1.last_modified))
+        .map(|(version, _)| version.clone());
+
+    let mut all_manifests = Vec::new();
+    for (version, version_objects) in &versions {
+        let mut files =
+            manifest::manifest_files(version_objects.objects.keys().map(String::as_str));
+        let reused =
+            manifest::reuse_checksums(&mut files, indexed.release(version), |file_name, asset| {
+                version_objects
+                    .objects
+                    .get(file_name)
+                    .is_some_and(|object| {
+                        asset.revision() == Some(object.etag.as_str())
+                            && client.object_uri(&object.key).ok().as_ref() == asset.download_uri()
+                    })
+            });
+        all_manifests.push(
+            future::join_all(
+                version_objects
+                    .objects
+                    .iter()
+                    .filter(|(file_name, _)| files.contains(file_name.as_str()))
+                    .map(|(file_name, object)| {
+                        client
+                            .manifest(object.key.clone(), file_name.clone())
+                            .map_err(|err| Error::Client(Box::new(err)))
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .map(move |mut manifests| {
+                manifests.push(reused);
+                manifests
+            }),
+        );
+    }
+
+    future::join_all(all_manifests).and_then(move |all_manifests| {
+        let mut releases = Vec::new();
+        for ((version, version_objects), mut manifests) in versions.into_iter().zip(all_manifests) {
+            if !manifest::has_targets(&manifests) {
+                manifests.extend(inference.manifests(
+                    version_objects.objects.keys().map(String::as_str),
+                    &repo_name,
+                ));
+            }
+            let targets = manifest::targets_from_manifests(manifests, |name, asset| {
+                let object = version_objects
+                    .objects
+                    .get(asset)
+                    .ok_or_else(|| Error::InvalidAssetPath(asset.to_string()))?;
+                let uri = client
+                    .object_uri(&object.key)
+                    .map_err(|err| Error::Client(Box::new(err)))?;
+
+                let mut asset = Asset::new(name, uri);
+                asset.set_size(Some(object.size));
+                asset.set_revision(Some(object.etag.as_str()));
+
+                Ok(asset)
+            })?;
+
+            let mut release = Release::new(release_id(&version), version);
+            release.set_created(parse_timestamp(&version_objects.last_modified));
+            release.set_targets(targets);
+            releases.push(release);
+        }
+
+        Ok((releases, latest))
+    })
+}
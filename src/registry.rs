@@ -1,9 +1,10 @@
-use crate::Provider;
+use crate::ReleaseSource;
 use std::collections::HashMap;
+use std::sync::Arc;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Registry {
-    providers: HashMap<String, Provider>,
+    providers: HashMap<String, Arc<dyn ReleaseSource>>,
 }
 
 impl Registry {
@@ -11,29 +12,23 @@ impl Registry {
         Self::default()
     }
 
-    pub fn register(&mut self, provider: Provider) {
+    pub fn register(&mut self, provider: Box<dyn ReleaseSource>) {
         let _ = self
             .providers
-            .insert(provider.domain().to_owned(), provider);
+            .insert(provider.domain().to_owned(), Arc::from(provider));
     }
 
-    pub fn get<S: AsRef<str>>(&self, key: S) -> Option<&Provider> {
-        self.providers.get(key.as_ref())
+    pub fn get<S: AsRef<str>>(&self, key: S) -> Option<Arc<dyn ReleaseSource>> {
+        self.providers.get(key.as_ref()).cloned()
     }
 
-    pub fn get_mut<S: AsRef<str>>(&mut self, key: S) -> Option<&mut Provider> {
-        self.providers.get_mut(key.as_ref())
+    /// Returns a provider for modification, unless it is shared with a previous registry or an
+    /// in-flight request.
+    pub fn get_mut<S: AsRef<str>>(&mut self, key: S) -> Option<&mut (dyn ReleaseSource + 'static)> {
+        self.providers.get_mut(key.as_ref()).and_then(Arc::get_mut)
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = &Provider> {
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn ReleaseSource>> {
         self.providers.values()
     }
 }
-
-impl Default for Registry {
-    fn default() -> Self {
-        Registry {
-            providers: HashMap::new(),
-        }
-    }
-}
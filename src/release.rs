@@ -1,4 +1,6 @@
-use crate::Target;
+use crate::{Target, TargetAliases};
+use chrono::{DateTime, Utc};
+use semver::Version;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::time::Instant;
@@ -7,16 +9,27 @@ use std::time::Instant;
 pub struct Release {
     id: u64,
     name: String,
+    version: Option<Version>,
+    prerelease: bool,
+    created: Option<DateTime<Utc>>,
     targets: HashMap<String, Target>,
+    aliases: HashMap<String, String>,
     updated: Option<Instant>,
 }
 
 impl Release {
     pub fn new<N: Into<String>>(id: u64, name: N) -> Self {
+        let name = name.into();
+        let version = parse_version(&name);
+
         Release {
             id,
-            name: name.into(),
+            name,
+            version,
+            prerelease: false,
+            created: None,
             targets: HashMap::new(),
+            aliases: HashMap::new(),
             updated: None,
         }
     }
@@ -29,21 +42,67 @@ impl Release {
         &self.name
     }
 
+    /// Returns the semantic version of the release, if its name is one.
+    ///
+    /// A leading `v`, as commonly used in tag names, is ignored.
+    pub fn version(&self) -> Option<&Version> {
+        self.version.as_ref()
+    }
+
+    pub fn prerelease(&self) -> bool {
+        self.prerelease
+    }
+
+    pub fn set_prerelease(&mut self, prerelease: bool) {
+        self.prerelease = prerelease;
+    }
+
+    pub fn created(&self) -> Option<DateTime<Utc>> {
+        self.created
+    }
+
+    pub fn set_created(&mut self, created: Option<DateTime<Utc>>) {
+        self.created = created;
+    }
+
+    /// Returns when the release's targets were last set, which is when it was last fetched with
+    /// changes.
+    pub fn updated(&self) -> Option<Instant> {
+        self.updated
+    }
+
     pub fn targets(&self) -> impl Iterator<Item = &Target> {
         self.targets.values()
     }
 
+    /// Returns the target with the given name, or else the target which has it as an alias.
     pub fn target<S: AsRef<str>>(&self, id: S) -> Option<&Target> {
-        self.targets.get(id.as_ref())
+        let id = id.as_ref();
+        self.targets
+            .get(id)
+            .or_else(|| self.aliases.get(id).and_then(|name| self.targets.get(name)))
+    }
+
+    /// Returns the first target found among the candidates for `id` in an alias table.
+    pub fn resolve_target<S: AsRef<str>>(&self, id: S, aliases: &TargetAliases) -> Option<&Target> {
+        aliases
+            .candidates(id.as_ref())
+            .iter()
+            .find_map(|candidate| self.target(candidate))
     }
 
     pub fn set_targets<V: Into<Vec<Target>>>(&mut self, vec: V) {
         let mut targets = HashMap::new();
+        let mut aliases = HashMap::new();
         for target in vec.into() {
+            for alias in target.aliases() {
+                aliases.insert(alias.clone(), target.name().to_string());
+            }
             targets.insert(target.name().to_string(), target);
         }
 
         self.targets = targets;
+        self.aliases = aliases;
         self.updated = Some(Instant::now());
     }
 }
@@ -61,3 +120,7 @@ impl Hash for Release {
         self.id.hash(state);
     }
 }
+
+fn parse_version(name: &str) -> Option<Version> {
+    Version::parse(name.trim_start_matches('v')).ok()
+}
@@ -1,19 +1,38 @@
+use crate::provider::infer::TargetInference;
 use crate::{ETag, Release};
+use semver::VersionReq;
+use serde::Deserialize;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::time::{Duration, Instant};
 
+/// How often a repo is updated unless configured otherwise.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The most by which a repo's first update is delayed unless configured otherwise.
+const DEFAULT_SPLAY: Duration = Duration::from_secs(30);
+
 #[derive(Debug, Clone)]
 pub struct Repo {
     owner: String,
     name: String,
-    releases: HashMap<String, Release>,
+    releases: Vec<Release>,
+    release_idx: HashMap<String, usize>,
     latest_id: Option<String>,
+    latest_prerelease_id: Option<String>,
     updated: Option<Instant>,
     releases_etag: Option<ETag>,
     latest_etag: Option<ETag>,
     interval: Duration,
+    splay: Duration,
+    prereleases: bool,
+    drafts: bool,
+    proxy: bool,
+    target_inference: TargetInference,
+    install_sh: Option<String>,
+    install_ps1: Option<String>,
 }
 
 impl Repo {
@@ -25,12 +44,21 @@ impl Repo {
         Repo {
             owner: owner.into(),
             name: name.into(),
-            releases: HashMap::new(),
+            releases: Vec::new(),
+            release_idx: HashMap::new(),
             latest_id: None,
+            latest_prerelease_id: None,
             updated: None,
             releases_etag: None,
             latest_etag: None,
-            interval: Duration::from_secs(30),
+            interval: DEFAULT_INTERVAL,
+            splay: DEFAULT_SPLAY,
+            prereleases: false,
+            drafts: false,
+            proxy: false,
+            target_inference: TargetInference::Off,
+            install_sh: None,
+            install_ps1: None,
         }
     }
 
@@ -42,31 +70,135 @@ impl Repo {
         &self.name
     }
 
+    /// Returns how often the repo is updated.
     pub fn interval(&self) -> Duration {
         self.interval
     }
 
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Returns the most by which the repo's first update is delayed, so that the updates of many
+    /// repos are spread out rather than all made at once.
+    pub fn splay(&self) -> Duration {
+        self.splay
+    }
+
+    pub fn set_splay(&mut self, splay: Duration) {
+        self.splay = splay;
+    }
+
+    /// Returns whether prereleases are included in the repo's releases.
+    pub fn prereleases(&self) -> bool {
+        self.prereleases
+    }
+
+    pub fn set_prereleases(&mut self, prereleases: bool) {
+        self.prereleases = prereleases;
+    }
+
+    /// Returns whether draft releases are included in the repo's releases.
+    ///
+    /// Providers only return drafts to clients which are authorized to see them.
+    pub fn drafts(&self) -> bool {
+        self.drafts
+    }
+
+    pub fn set_drafts(&mut self, drafts: bool) {
+        self.drafts = drafts;
+    }
+
+    /// Returns whether remote assets are streamed through the server rather than redirected to.
+    pub fn proxy(&self) -> bool {
+        self.proxy
+    }
+
+    pub fn set_proxy(&mut self, proxy: bool) {
+        self.proxy = proxy;
+    }
+
+    /// Returns how targets are found for releases which publish no manifest.
+    pub fn target_inference(&self) -> &TargetInference {
+        &self.target_inference
+    }
+
+    pub fn set_target_inference(&mut self, target_inference: TargetInference) {
+        self.target_inference = target_inference;
+    }
+
+    /// Returns the template of the repo's shell installer, if it overrides the built-in one.
+    pub fn install_sh(&self) -> Option<&str> {
+        self.install_sh.as_deref()
+    }
+
+    pub fn set_install_sh<S: Into<String>>(&mut self, template: Option<S>) {
+        self.install_sh = template.map(Into::into);
+    }
+
+    /// Returns the template of the repo's PowerShell installer, if it overrides the built-in one.
+    pub fn install_ps1(&self) -> Option<&str> {
+        self.install_ps1.as_deref()
+    }
+
+    pub fn set_install_ps1<S: Into<String>>(&mut self, template: Option<S>) {
+        self.install_ps1 = template.map(Into::into);
+    }
+
+    /// Returns the releases, newest first.
     pub fn releases(&self) -> impl Iterator<Item = &Release> {
-        // TODO: sort releases by created_at
-        self.releases.values()
+        self.releases.iter()
     }
 
-    pub fn set_releases<V: Into<Vec<Release>>>(&mut self, vec: V) {
-        let mut releases = HashMap::new();
-        for release in vec.into() {
-            releases.insert(release.name().to_string(), release);
+    /// Returns the releases in the given order.
+    pub fn releases_by(&self, order: ReleaseOrder) -> Vec<&Release> {
+        let mut releases = self.releases.iter().collect::<Vec<_>>();
+        match order {
+            ReleaseOrder::Created => {}
+            ReleaseOrder::Semver => releases.sort_by(|a, b| cmp_semver(b, a)),
+            ReleaseOrder::Name => releases.sort_by(|a, b| a.name().cmp(b.name())),
         }
 
+        releases
+    }
+
+    pub fn set_releases<V: Into<Vec<Release>>>(&mut self, vec: V) {
+        let mut releases = vec.into();
+        releases.sort_by(|a, b| cmp_created(b, a));
+
+        self.release_idx = releases
+            .iter()
+            .enumerate()
+            .map(|(idx, release)| (release.name().to_string(), idx))
+            .collect();
         self.releases = releases;
     }
 
     pub fn release<N: AsRef<str>>(&self, name: N) -> Option<&Release> {
-        self.releases.get(name.as_ref())
+        self.release_idx
+            .get(name.as_ref())
+            .map(|idx| &self.releases[*idx])
+    }
+
+    /// Returns the release with the highest semantic version which matches a requirement.
+    ///
+    /// A prerelease version is only matched when the requirement names a prerelease of the same
+    /// version, such as `^1.4.0-rc.1`. Releases which are marked as prereleases without having a
+    /// prerelease version are never matched.
+    pub fn release_matching(&self, req: &VersionReq) -> Option<&Release> {
+        self.releases
+            .iter()
+            .filter_map(|release| release.version().map(|version| (version, release)))
+            .filter(|(version, release)| {
+                (!release.prerelease() || version.is_prerelease()) && req.matches(version)
+            })
+            .max_by(|a, b| a.0.cmp(b.0))
+            .map(|(_, release)| release)
     }
 
     pub fn latest_release(&self) -> Option<&Release> {
         match self.latest_id {
-            Some(ref id) => self.releases.get(id),
+            Some(ref id) => self.release(id),
             None => None,
         }
     }
@@ -75,6 +207,27 @@ impl Repo {
         self.latest_id = id.map(|id| id.into());
     }
 
+    /// Returns the most recent release, including prereleases.
+    pub fn latest_prerelease(&self) -> Option<&Release> {
+        match self.latest_prerelease_id {
+            Some(ref id) => self.release(id),
+            None => None,
+        }
+    }
+
+    pub fn set_latest_prerelease<S: Into<String>>(&mut self, id: Option<S>) {
+        self.latest_prerelease_id = id.map(|id| id.into());
+    }
+
+    /// Returns when the repo was last updated successfully, whether or not its releases changed.
+    pub fn updated(&self) -> Option<Instant> {
+        self.updated
+    }
+
+    pub fn set_updated(&mut self, updated: Option<Instant>) {
+        self.updated = updated;
+    }
+
     pub fn releases_etag(&self) -> Option<&ETag> {
         self.releases_etag.as_ref()
     }
@@ -90,6 +243,18 @@ impl Repo {
     pub fn set_latest_etag(&mut self, etag: Option<ETag>) {
         self.latest_etag = etag;
     }
+
+    /// Replaces the releases, latest release ids, update time and entity tags with those of a
+    /// snapshot of the repo, keeping the repo's own config.
+    pub fn restore_index(&mut self, snapshot: Repo) {
+        self.releases = snapshot.releases;
+        self.release_idx = snapshot.release_idx;
+        self.latest_id = snapshot.latest_id;
+        self.latest_prerelease_id = snapshot.latest_prerelease_id;
+        self.updated = snapshot.updated;
+        self.releases_etag = snapshot.releases_etag;
+        self.latest_etag = snapshot.latest_etag;
+    }
 }
 
 impl PartialEq for Repo {
@@ -112,3 +277,167 @@ impl fmt::Display for Repo {
         write!(f, "{}/{}", self.owner, self.name)
     }
 }
+
+/// The order in which the releases of a repo are listed.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReleaseOrder {
+    /// Newest release first, which is the default.
+    #[default]
+    Created,
+    /// Highest semantic version first, followed by releases which aren't semantic versions.
+    Semver,
+    /// Alphabetically by name.
+    Name,
+}
+
+/// Compares releases by creation time, falling back to their names when the times are equal or
+/// unknown. Releases without a creation time are ordered before those with one.
+fn cmp_created(a: &Release, b: &Release) -> Ordering {
+    a.created()
+        .cmp(&b.created())
+        .then_with(|| a.name().cmp(b.name()))
+}
+
+/// Compares releases by semantic version, falling back to their names. Releases which aren't
+/// semantic versions are ordered before those which are.
+fn cmp_semver(a: &Release, b: &Release) -> Ordering {
+    a.version()
+        .cmp(&b.version())
+        .then_with(|| a.name().cmp(b.name()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn release(id: u64, name: &str, prerelease: bool) -> Release {
+        let mut release = Release::new(id, name);
+        release.set_prerelease(prerelease);
+
+        release
+    }
+
+    fn matching<'a>(repo: &'a Repo, req: &str) -> Option<&'a str> {
+        repo.release_matching(&VersionReq::parse(req).unwrap())
+            .map(Release::name)
+    }
+
+    fn semver_repo() -> Repo {
+        let mut repo = Repo::new("acme", "tool");
+        repo.set_releases(vec![
+            release(1, "v1.1.0", false),
+            release(2, "v1.2.0", false),
+            release(3, "v1.2.5", false),
+            release(4, "v1.3.0", false),
+            release(5, "v1.4.0-rc.1", true),
+            release(6, "v2.0.0", true),
+            release(7, "nightly", false),
+        ]);
+
+        repo
+    }
+
+    #[test]
+    fn release_matching_highest() {
+        let repo = semver_repo();
+
+        assert_eq!(matching(&repo, "^1"), Some("v1.3.0"));
+        assert_eq!(matching(&repo, "~1.2"), Some("v1.2.5"));
+        assert_eq!(matching(&repo, "=1.1.0"), Some("v1.1.0"));
+        assert_eq!(matching(&repo, "<1.2.5"), Some("v1.2.0"));
+        assert_eq!(matching(&repo, "*"), Some("v1.3.0"));
+    }
+
+    #[test]
+    fn release_matching_none() {
+        let repo = semver_repo();
+
+        assert_eq!(matching(&repo, "^3"), None);
+        assert_eq!(matching(&repo, ">1.3.0, <1.4.0"), None);
+    }
+
+    #[test]
+    fn release_matching_prereleases() {
+        let repo = semver_repo();
+
+        // Prereleases are only matched when the requirement names one
+        assert_eq!(matching(&repo, ">=1.3.1"), None);
+        assert_eq!(matching(&repo, "^1.4.0-rc.0"), Some("v1.4.0-rc.1"));
+        assert_eq!(matching(&repo, "=1.4.0-rc.1"), Some("v1.4.0-rc.1"));
+        // A release marked as a prerelease without a prerelease version is never matched
+        assert_eq!(matching(&repo, "^2"), None);
+        assert_eq!(matching(&repo, "=2.0.0"), None);
+    }
+
+    fn created(id: u64, name: &str, created: Option<i64>) -> Release {
+        let mut release = Release::new(id, name);
+        release.set_created(created.map(|secs| Utc.timestamp_opt(secs, 0).unwrap()));
+
+        release
+    }
+
+    fn names(releases: Vec<&Release>) -> Vec<&str> {
+        releases.into_iter().map(Release::name).collect()
+    }
+
+    fn sorting_repo() -> Repo {
+        let mut repo = Repo::new("acme", "tool");
+        repo.set_releases(vec![
+            created(1, "v1.10.0", Some(300)),
+            created(2, "untagged", None),
+            created(3, "v1.9.0", Some(400)),
+            created(4, "v2.0.0", Some(200)),
+            created(5, "b-tie", Some(500)),
+            created(6, "a-tie", Some(500)),
+            created(7, "nightly", None),
+        ]);
+
+        repo
+    }
+
+    #[test]
+    fn releases_newest_first() {
+        let repo = sorting_repo();
+        let expected = vec![
+            "b-tie", "a-tie", "v1.9.0", "v1.10.0", "v2.0.0", "untagged", "nightly",
+        ];
+
+        assert_eq!(names(repo.releases().collect()), expected);
+        assert_eq!(names(repo.releases_by(ReleaseOrder::Created)), expected);
+        assert_eq!(
+            repo.releases_by(ReleaseOrder::default()).len(),
+            expected.len()
+        );
+    }
+
+    #[test]
+    fn releases_by_semver() {
+        assert_eq!(
+            names(sorting_repo().releases_by(ReleaseOrder::Semver)),
+            vec!["v2.0.0", "v1.10.0", "v1.9.0", "untagged", "nightly", "b-tie", "a-tie"]
+        );
+    }
+
+    #[test]
+    fn releases_by_name() {
+        assert_eq!(
+            names(sorting_repo().releases_by(ReleaseOrder::Name)),
+            vec!["a-tie", "b-tie", "nightly", "untagged", "v1.10.0", "v1.9.0", "v2.0.0"]
+        );
+    }
+
+    #[test]
+    fn set_releases_reindexes() {
+        let mut repo = sorting_repo();
+        repo.set_releases(vec![
+            created(8, "v3.0.0", Some(600)),
+            created(1, "v1.10.0", Some(300)),
+        ]);
+
+        assert_eq!(repo.release("v3.0.0").map(Release::id), Some(8));
+        assert_eq!(repo.release("v1.10.0").map(Release::id), Some(1));
+        assert!(repo.release("v2.0.0").is_none());
+    }
+}
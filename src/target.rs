@@ -1,5 +1,5 @@
 use crate::Asset;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::hash::{Hash, Hasher};
 
@@ -7,6 +7,7 @@ use std::hash::{Hash, Hasher};
 pub struct Target {
     name: String,
     assets: HashMap<String, Asset>,
+    aliases: Vec<String>,
 }
 
 impl Target {
@@ -14,6 +15,7 @@ impl Target {
         Target {
             name: name.into(),
             assets: HashMap::new(),
+            aliases: Vec::new(),
         }
     }
 
@@ -21,6 +23,18 @@ impl Target {
         &self.name
     }
 
+    /// Returns the other names which the target can be requested by.
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    pub fn push_alias<S: Into<String>>(&mut self, alias: S) {
+        let alias = alias.into();
+        if alias != self.name && !self.aliases.contains(&alias) {
+            self.aliases.push(alias);
+        }
+    }
+
     pub fn assets(&self) -> impl Iterator<Item = &Asset> {
         self.assets.values()
     }
@@ -62,3 +76,229 @@ impl fmt::Display for Target {
         write!(f, "{}", self.name)
     }
 }
+
+/// Built-in aliases for the `os-arch` pairs reported by `uname -sm` and similar, mapped to the
+/// Rust target triples they most commonly stand for.
+const BUILTIN_ALIASES: &[(&str, &str)] = &[
+    ("darwin-aarch64", "aarch64-apple-darwin"),
+    ("darwin-arm64", "aarch64-apple-darwin"),
+    ("darwin-x86_64", "x86_64-apple-darwin"),
+    ("freebsd-amd64", "x86_64-unknown-freebsd"),
+    ("freebsd-x86_64", "x86_64-unknown-freebsd"),
+    ("linux-aarch64", "aarch64-unknown-linux-gnu"),
+    ("linux-arm64", "aarch64-unknown-linux-gnu"),
+    ("linux-armv7", "armv7-unknown-linux-gnueabihf"),
+    ("linux-armv7l", "armv7-unknown-linux-gnueabihf"),
+    ("linux-amd64", "x86_64-unknown-linux-gnu"),
+    ("linux-i386", "i686-unknown-linux-gnu"),
+    ("linux-i686", "i686-unknown-linux-gnu"),
+    ("linux-x86_64", "x86_64-unknown-linux-gnu"),
+    ("windows-aarch64", "aarch64-pc-windows-msvc"),
+    ("windows-amd64", "x86_64-pc-windows-msvc"),
+    ("windows-arm64", "aarch64-pc-windows-msvc"),
+    ("windows-i686", "i686-pc-windows-msvc"),
+    ("windows-x86", "i686-pc-windows-msvc"),
+    ("windows-x86_64", "x86_64-pc-windows-msvc"),
+];
+
+/// The `gnu` environments of target triples, along with their `musl` counterparts.
+const MUSL_FALLBACKS: &[(&str, &str)] = &[
+    ("-gnueabihf", "-musleabihf"),
+    ("-gnueabi", "-musleabi"),
+    ("-gnu", "-musl"),
+];
+
+/// A table of aliases which targets can be requested by, such as `linux-x86_64`.
+///
+/// Each alias maps to one or more targets, in order of preference. Aliases are matched without
+/// regard to case, as clients commonly build them from the output of `uname`.
+#[derive(Debug, Clone)]
+pub struct TargetAliases {
+    aliases: HashMap<String, Vec<String>>,
+    musl_fallback: bool,
+}
+
+impl TargetAliases {
+    /// Returns a table with no aliases.
+    pub fn empty() -> Self {
+        TargetAliases {
+            aliases: HashMap::new(),
+            musl_fallback: false,
+        }
+    }
+
+    /// Adds an alias, replacing any existing alias of the same name.
+    pub fn insert<S: AsRef<str>>(&mut self, alias: S, targets: Vec<String>) {
+        self.aliases
+            .insert(alias.as_ref().to_ascii_lowercase(), targets);
+    }
+
+    /// Returns whether a missing `gnu` target falls back to its `musl` counterpart.
+    pub fn musl_fallback(&self) -> bool {
+        self.musl_fallback
+    }
+
+    pub fn set_musl_fallback(&mut self, musl_fallback: bool) {
+        self.musl_fallback = musl_fallback;
+    }
+
+    /// Returns the targets to look for, in order, when the target `id` is requested.
+    ///
+    /// The requested target itself is always tried first, followed by the targets of any alias
+    /// by that name and then, when enabled, the `musl` counterparts of any `gnu` targets.
+    pub fn candidates(&self, id: &str) -> Vec<String> {
+        let mut candidates = vec![id.to_string()];
+        if let Some(targets) = self.aliases.get(&id.to_ascii_lowercase()) {
+            candidates.extend(targets.iter().cloned());
+        }
+
+        if self.musl_fallback {
+            let fallbacks = candidates
+                .iter()
+                .filter_map(|candidate| {
+                    MUSL_FALLBACKS.iter().find_map(|(gnu, musl)| {
+                        if candidate.ends_with(gnu) {
+                            Some(format!(
+                                "{}{}",
+                                &candidate[..candidate.len() - gnu.len()],
+                                musl
+                            ))
+                        } else {
+                            None
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            candidates.extend(fallbacks);
+        }
+
+        let mut seen = HashSet::new();
+        candidates.retain(|candidate| seen.insert(candidate.clone()));
+
+        candidates
+    }
+}
+
+impl Default for TargetAliases {
+    /// Returns a table with the built-in aliases.
+    fn default() -> Self {
+        let mut aliases = Self::empty();
+        for (alias, target) in BUILTIN_ALIASES {
+            aliases.insert(alias, vec![target.to_string()]);
+        }
+
+        aliases
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_aliases() {
+        let aliases = TargetAliases::default();
+
+        assert_eq!(
+            aliases.candidates("linux-x86_64"),
+            vec!["linux-x86_64", "x86_64-unknown-linux-gnu"]
+        );
+        assert_eq!(
+            aliases.candidates("darwin-arm64"),
+            vec!["darwin-arm64", "aarch64-apple-darwin"]
+        );
+        assert_eq!(
+            aliases.candidates("x86_64-unknown-linux-gnu"),
+            vec!["x86_64-unknown-linux-gnu"]
+        );
+        assert!(!aliases.musl_fallback());
+    }
+
+    #[test]
+    fn aliases_ignore_case() {
+        let mut aliases = TargetAliases::empty();
+        aliases.insert(
+            "Linux-Universal",
+            vec!["x86_64-unknown-linux-gnu".to_string()],
+        );
+
+        assert_eq!(
+            aliases.candidates("linux-universal"),
+            vec!["linux-universal", "x86_64-unknown-linux-gnu"]
+        );
+        assert_eq!(
+            TargetAliases::default().candidates("Linux-X86_64"),
+            vec!["Linux-X86_64", "x86_64-unknown-linux-gnu"]
+        );
+    }
+
+    #[test]
+    fn alias_replaced_in_order() {
+        let mut aliases = TargetAliases::default();
+        aliases.insert(
+            "linux-x86_64",
+            vec![
+                "x86_64-unknown-linux-musl".to_string(),
+                "x86_64-unknown-linux-gnu".to_string(),
+            ],
+        );
+
+        assert_eq!(
+            aliases.candidates("linux-x86_64"),
+            vec![
+                "linux-x86_64",
+                "x86_64-unknown-linux-musl",
+                "x86_64-unknown-linux-gnu"
+            ]
+        );
+        assert_eq!(
+            TargetAliases::empty().candidates("linux-x86_64"),
+            vec!["linux-x86_64"]
+        );
+    }
+
+    #[test]
+    fn musl_fallback() {
+        let mut aliases = TargetAliases::default();
+        aliases.set_musl_fallback(true);
+
+        assert_eq!(
+            aliases.candidates("x86_64-unknown-linux-gnu"),
+            vec!["x86_64-unknown-linux-gnu", "x86_64-unknown-linux-musl"]
+        );
+        assert_eq!(
+            aliases.candidates("linux-armv7"),
+            vec![
+                "linux-armv7",
+                "armv7-unknown-linux-gnueabihf",
+                "armv7-unknown-linux-musleabihf"
+            ]
+        );
+        assert_eq!(
+            aliases.candidates("x86_64-pc-windows-msvc"),
+            vec!["x86_64-pc-windows-msvc"]
+        );
+    }
+
+    #[test]
+    fn musl_fallback_deduplicated() {
+        let mut aliases = TargetAliases::empty();
+        aliases.set_musl_fallback(true);
+        aliases.insert(
+            "linux",
+            vec![
+                "x86_64-unknown-linux-gnu".to_string(),
+                "x86_64-unknown-linux-musl".to_string(),
+            ],
+        );
+
+        assert_eq!(
+            aliases.candidates("linux"),
+            vec![
+                "linux",
+                "x86_64-unknown-linux-gnu",
+                "x86_64-unknown-linux-musl"
+            ]
+        );
+    }
+}